@@ -1,6 +1,6 @@
 use crate::{
   stains::ai::chain,
-  common::types::AOptions,
+  common::{ conf, db, migrate, roster, types::AOptions, state::{ self, Slot } },
   handler::Handler,
   commands::{
     meta::*,
@@ -25,15 +25,10 @@ use serenity::{
   model::{channel::{Message}}
 };
 
-use argparse::{
-  ArgumentParser,
-  action::{IFlagAction, ParseResult}
-};
-
-use env_logger::Env;
+use tokio::signal::unix::{ signal, SignalKind };
 
 use std::collections::HashSet;
-use std::sync::Arc;
+use std::sync::{ Arc, atomic::Ordering };
 
 use regex::Regex;
 
@@ -43,24 +38,12 @@ use rand::{
   SeedableRng
 };
 
-pub struct Version();
-
-impl IFlagAction for Version {
-  fn parse_flag(&self) -> ParseResult {
-    set!( version = env!("CARGO_PKG_VERSION").to_string()
-        , pname = "Amadeus"
-        , version_string = format!("{} {}", pname, version) );
-    println!("{}", version_string);
-    return ParseResult::Exit;
-  }
-}
-
 #[group]
 #[commands(ping, help, embed, qrcode, urban)]
 struct Meta;
 
 #[group]
-#[commands(quote, score, give)]
+#[commands(quote, score, top, give, history)]
 struct Chat;
 
 #[group]
@@ -72,18 +55,18 @@ struct Voice;
 struct Warcraft;
 
 #[group]
-#[commands(stats, ongoing)]
+#[commands(stats, ongoing, link, unlink, standings, match_summary)]
 struct Pad;
 
 #[group]
 #[owners_only]
 #[checks(Admin)]
-#[commands(say, set, clear)]
+#[commands(say, set, clear, backup, restore, reload)]
 struct Owner;
 
 #[group]
 #[checks(Admin)]
-#[commands(idle, stream)]
+#[commands(idle, stream, tiers, resync, audit, pointrules, grant, revoke, setpoints, reset, multiplier, multipliers, config, roster, approve)]
 struct Admin;
 
 #[check]
@@ -168,22 +151,21 @@ async fn unrecognised_command(ctx: &Context, msg: &Message, _command_name: &str)
   }
 }
 
-pub async fn run(opts : &AOptions) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-  { // this block limits scope of borrows by ap.refer() method
-    let mut ap = ArgumentParser::new();
-    let pname = "Amadeus";
-    ap.set_description(pname);
-    ap.add_option(&["--version"], Version(), "Show version");
-    ap.parse_args_or_exit();
+/// Connects to Discord and runs until shutdown
+pub async fn run(opts: &AOptions, conf_file: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  conf::check_token(opts)?;
+
+  db::open(opts.db_path.as_str(), opts.db_capacity)?;
+  migrate::run().await?;
+  state::migrate_voice(conf::legacy_voice(conf_file)).await?;
+  roster::seed().await?;
+  chain::ACTIVITY_LEVEL.store(opts.activity, Ordering::Relaxed);
+  if let Some(level) = state::load::<u32>(Slot::Activity).await? {
+    chain::ACTIVITY_LEVEL.store(level, Ordering::Relaxed);
+  }
+  if let Some(season) = state::load::<u32>(Slot::Season).await? {
+    CURRENT_SEASON.store(season, Ordering::Relaxed);
   }
-
-  let env = Env::default()
-    .filter_or("MY_LOG_LEVEL", "info") // trace
-    .write_style_or("MY_LOG_STYLE", "always");
-
-  env_logger::init_from_env(env);
-
-  info!("Amadeus {}", env!("CARGO_PKG_VERSION").to_string());
 
   let http = serenity::http::Http::new_with_token(&opts.discord);
 
@@ -203,7 +185,7 @@ pub async fn run(opts : &AOptions) -> Result<(), Box<dyn std::error::Error + Sen
      .configure(|c| c
       .owners(owners)
       .on_mention(Some(bot_id))
-      .prefix("~")
+      .prefix(opts.prefix.as_str())
       .delimiters(vec![" "])
       .case_insensitivity(true))
       .on_dispatch_error(on_dispatch_error)
@@ -218,7 +200,7 @@ pub async fn run(opts : &AOptions) -> Result<(), Box<dyn std::error::Error + Sen
       .group(&ADMIN_GROUP);
 
   let mut client = serenity::Client::new(&opts.discord)
-                    .event_handler(Handler::new(&opts))
+                    .event_handler(Handler::new(opts))
                     .framework(std_framework).await?;
   {
     let mut data = client.data.write().await;
@@ -226,6 +208,21 @@ pub async fn run(opts : &AOptions) -> Result<(), Box<dyn std::error::Error + Sen
     data.insert::<ShardManagerContainer>(Arc::clone(&client.shard_manager));
   }
 
+  // kill -HUP reloads config
+  tokio::spawn(async {
+    match signal(SignalKind::hangup()) {
+      Ok(mut hangups) =>
+        while hangups.recv().await.is_some() {
+          match reload_config().await {
+            Ok(reloaded) => info!("config reloaded, applied: {:?}, needs restart: {:?}"
+                                 , reloaded.applied, reloaded.restart),
+            Err(why) => error!("Failed to reload config {}", why)
+          }
+        },
+      Err(why) => error!("Failed to listen for SIGHUP {:?}", why)
+    }
+  });
+
   // start listening for events by starting a single shard
   if let Err(why) = client.start_autosharded().await {
     eprintln!("An error occurred while running the client: {:?}", why);
@@ -0,0 +1,203 @@
+/*
+* Command line
+* amadeus [--config conf.ini] <command> [arguments]
+* everything besides `run` works offline and never connects to Discord
+*/
+
+use crate::{
+  amadeus,
+  stains::ai::chain,
+  common::{
+    conf, db, migrate, backup,
+    db::{ DB, keys::{ Key, Kind, Table } },
+    types::AOptions
+  }
+};
+
+use argparse::{
+  ArgumentParser, Store, StoreTrue, List,
+  action::{IFlagAction, ParseResult}
+};
+
+use env_logger::Env;
+
+use std::{
+  collections::BTreeMap,
+  fs, io, process
+};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+pub struct Version();
+
+impl IFlagAction for Version {
+  fn parse_flag(&self) -> ParseResult {
+    set!( version = env!("CARGO_PKG_VERSION").to_string()
+        , pname = "Amadeus"
+        , version_string = format!("{} {}", pname, version) );
+    println!("{}", version_string);
+    return ParseResult::Exit;
+  }
+}
+
+static COMMANDS : &str =
+"Commands:
+  run             connect to Discord (default)
+  check-config    validate configuration and exit
+  export-points   write points of guild to file or stdout
+  import-points   read points of guild from file
+  storage-info    show what is stored in database
+  train-chain     feed text file to chat AI";
+
+/// parses arguments of subcommand, prints help and exits on `--help` or error
+fn parse_or_exit(ap: &ArgumentParser, command: &str, mut args: Vec<String>) {
+  args.insert(0, format!("Amadeus {}", command));
+  if let Err(code) = ap.parse(args, &mut io::stdout(), &mut io::stderr()) {
+    process::exit(code);
+  }
+}
+
+/// config and database for offline commands
+async fn open_db(conf_file: &str) -> Result<AOptions> {
+  let opts = conf::parse_config(conf_file)?;
+  db::open(opts.db_path.as_str(), opts.db_capacity)?;
+  migrate::run().await?;
+  Ok(opts)
+}
+
+async fn check_config(conf_file: &str) -> Result<()> {
+  let opts = conf::parse_config(conf_file)?;
+  conf::check_token(&opts)?;
+  println!("{} is fine", conf_file);
+  println!("prefix: {}, guild: {}, gateway: {}", opts.prefix, opts.guild, opts.gateway);
+  println!("database: {} ({} bytes)", opts.db_path, opts.db_capacity);
+  Ok(())
+}
+
+async fn points_file(conf_file: &str, import: bool, args: Vec<String>) -> Result<()> {
+  setm!{ guild     = 0u64
+       , file      = String::new()
+       , format    = String::new()
+       , overwrite = false };
+  {
+    let mut ap = ArgumentParser::new();
+    ap.refer(&mut guild).required()
+      .add_argument("guild", Store, "Guild id");
+    ap.refer(&mut file)
+      .add_option(&["--file"], Store, "Points file (export goes to stdout without it)");
+    ap.refer(&mut format)
+      .add_option(&["--format"], Store, "json or csv, guessed by file name by default");
+    if import {
+      ap.refer(&mut overwrite)
+        .add_option(&["--overwrite"], StoreTrue, "Clear points of members missing in file");
+    }
+    parse_or_exit(&ap, if import { "import-points" } else { "export-points" }, args);
+  }
+  if import && file.is_empty() {
+    return Err("import needs --file".into());
+  }
+  let format = if format.is_empty() {
+      backup::Format::from_file_name(file.as_str())
+    } else {
+      format.parse::<backup::Format>()?
+    };
+  open_db(conf_file).await?;
+  if import {
+    backup::import_from_file(guild, format, file.as_str(), overwrite).await?;
+  } else {
+    backup::export_to_file(guild, format, file.as_str()).await?;
+  }
+  Ok(())
+}
+
+async fn storage_info(conf_file: &str) -> Result<()> {
+  // no migrations here, shows database as it is
+  let opts = conf::parse_config(conf_file)?;
+  db::open(opts.db_path.as_str(), opts.db_capacity)?;
+  let size = fs::metadata(opts.db_path.as_str()).map(|m| m.len()).unwrap_or(0);
+  let (keys, schema) = DB.transaction(|tx| -> std::result::Result<(Vec<Key>, u32), db::Error> {
+    let (from, to) = Key::all();
+    let keys = tx.range(&from, &to)?;
+    let schema = tx.load(&Key::table(Table::Schema, 0))?.unwrap_or(0);
+    Ok((keys, schema))
+  }).await?;
+  let mut kinds: BTreeMap<String, usize> = BTreeMap::new();
+  for key in &keys {
+    let kind = match key.kind() {
+      Kind::Table(t) => format!("table {}", t),
+      other          => format!("{:?}", other).to_lowercase()
+    };
+    *kinds.entry(kind).or_insert(0) += 1;
+  }
+  println!("database: {}", opts.db_path);
+  println!("file size: {} of {} bytes", size, opts.db_capacity);
+  println!("schema: {}", schema);
+  println!("records: {}", keys.len());
+  for (kind, count) in kinds {
+    println!("  {}: {}", kind, count);
+  }
+  Ok(())
+}
+
+async fn train_chain(conf_file: &str, args: Vec<String>) -> Result<()> {
+  let mut corpus = String::new();
+  {
+    let mut ap = ArgumentParser::new();
+    ap.set_description("Lines of text file are used by chat AI together with messages");
+    ap.refer(&mut corpus).required()
+      .add_argument("corpus", Store, "Text file, one phrase per line");
+    parse_or_exit(&ap, "train-chain", args);
+  }
+  let text = fs::read_to_string(corpus.as_str())?;
+  open_db(conf_file).await?;
+  let lines = text.lines().map(str::to_string).collect();
+  let size = chain::store_corpus(lines).await?;
+  println!("corpus has {} lines now", size);
+  Ok(())
+}
+
+pub async fn run() -> Result<()> {
+  setm!{ conf_file = String::from(conf::CONF_FILE_NAME)
+       , command   = String::from("run")
+       , args      = Vec::<String>::new() };
+  { // this block limits scope of borrows by ap.refer() method
+    let mut ap = ArgumentParser::new();
+    ap.set_description(COMMANDS);
+    ap.add_option(&["--version"], Version(), "Show version");
+    ap.refer(&mut conf_file)
+      .add_option(&["--config"], Store, "Configuration file (conf.ini by default)");
+    ap.refer(&mut command)
+      .add_argument("command", Store, "Command to run, see above");
+    ap.refer(&mut args)
+      .add_argument("arguments", List, "Arguments of command, see <command> --help");
+    ap.stop_on_first_argument(true);
+    ap.parse_args_or_exit();
+  }
+
+  let env = Env::default()
+    .filter_or("MY_LOG_LEVEL", "info") // trace
+    .write_style_or("MY_LOG_STYLE", "always");
+
+  env_logger::init_from_env(env);
+
+  match command.as_str() {
+    "run" => {
+      // run takes --config after command as well
+      {
+        let mut ap = ArgumentParser::new();
+        ap.refer(&mut conf_file)
+          .add_option(&["--config"], Store, "Configuration file (conf.ini by default)");
+        parse_or_exit(&ap, "run", args);
+      }
+      info!("Amadeus {}", env!("CARGO_PKG_VERSION").to_string());
+      let opts = conf::parse_config(conf_file.as_str())?;
+      amadeus::run(&opts, conf_file.as_str()).await
+    },
+    "check-config"  => check_config(conf_file.as_str()).await,
+    "export-points" => points_file(conf_file.as_str(), false, args).await,
+    "import-points" => points_file(conf_file.as_str(), true, args).await,
+    "storage-info"  => storage_info(conf_file.as_str()).await,
+    "train-chain"   => train_chain(conf_file.as_str(), args).await,
+    other => Err(format!("unknown command {}\n{}", other, COMMANDS).into())
+  }
+}
@@ -4,6 +4,7 @@ use crate::common::types::{ Player, player, streamer, streams, twitch };
 * Note all the members of those lists are agreed to share this imformation
 * If someone wants to be excluded please feel free to contact author or do it with PR
 * Don't be toxic
+* Those are only initial roster, it's changed with ~roster after first start
 */
 
 pub static DIVISION1: &'static [Player] = &
@@ -40,7 +41,3 @@ pub static INTERESTING: &'static [Player] = &
   , streamer("РозовыйПони#228941", 279559886931492865, streams("JosephStalin", "sqktgw"))
   , streamer("ag3nt#21617",        293268448212156416, streams("ag3nt", "ag3ntik"))
   ];
-
-pub fn players() -> Vec<Player> {
-  [DIVISION1, DIVISION2, INTERESTING].concat()
-}
@@ -1,5 +1,20 @@
+use crate::{
+  common::{
+    conf, points, tiers, ledger, antifarm, multipliers, log, settings,
+    roster::{ self, Division, Member }, links,
+    types::{ Player, Streams },
+    msg::{ channel_message }
+  },
+  collections::channels::{ AI_ALLOWED, AI_LEARN },
+  stains::{
+    ai::chain::ACTIVITY_LEVEL,
+    cyber::utils::{ get_gateway, get_gateway_name }
+  }
+};
+
 use serenity::{
-  model::{ channel::*, gateway::Activity },
+  model::{ channel::*, gateway::Activity
+         , id::{ ChannelId, RoleId, UserId }, misc::Mentionable },
   prelude::*,
   framework::standard::{
     Args, CommandResult,
@@ -7,6 +22,11 @@ use serenity::{
   }
 };
 
+use std::{
+  borrow::Cow,
+  sync::atomic::Ordering
+};
+
 #[command]
 async fn idle(ctx: &Context, msg: &Message, args : Args) -> CommandResult {
   let what = args.message();
@@ -30,3 +50,597 @@ async fn stream(ctx: &Context, msg: &Message, mut args : Args) -> CommandResult
   }
   Ok(())
 }
+
+/// `~tiers` lists point tiers
+/// `~tiers set <points> <@role>` adds tier or moves role to another threshold
+/// `~tiers remove <@role>` removes tier (role itself stays on members until resync)
+#[command]
+async fn tiers(ctx: &Context, msg: &Message, mut args : Args) -> CommandResult {
+  if let Some(guild_id) = msg.guild_id {
+    let guild_u64 = guild_id.as_u64().clone();
+    let mut guild_tiers = points::get_tiers(guild_u64).await?;
+    if let Ok(action) = args.single::<String>() {
+      match action.as_str() {
+        "set" => {
+          set!{ threshold = args.single::<u64>()
+              , role      = msg.mention_roles.first() };
+          if let (Ok(threshold), Some(role)) = (threshold, role) {
+            guild_tiers.retain(|t| t.role != role.0);
+            guild_tiers.push(points::Tier { points: threshold, role: role.0 });
+            points::set_tiers(guild_u64, guild_tiers.clone()).await?;
+          } else {
+            channel_message(ctx, msg, "usage: tiers set <points> <@role>").await;
+            return Ok(());
+          }
+        },
+        "remove" => {
+          if let Some(role) = msg.mention_roles.first() {
+            guild_tiers.retain(|t| t.role != role.0);
+            points::set_tiers(guild_u64, guild_tiers.clone()).await?;
+          } else {
+            channel_message(ctx, msg, "usage: tiers remove <@role>").await;
+            return Ok(());
+          }
+        },
+        _ => {
+          channel_message(ctx, msg, "tiers could be only set or removed").await;
+          return Ok(());
+        }
+      }
+    }
+    guild_tiers.sort_by_key(|t| t.points);
+    let description = if guild_tiers.is_empty() {
+        String::from("no tiers are set")
+      } else {
+        guild_tiers.iter()
+                   .map(|t| format!("**{}** points: {}", t.points, RoleId(t.role).mention()))
+                   .collect::<Vec<String>>()
+                   .join("\n")
+      };
+    if let Err(why) = msg.channel_id.send_message(ctx, |m| m
+      .embed(|e| e
+      .title("Point tiers")
+      .description(description)
+    )).await {
+      error!("Failed to post tiers {:?}", why);
+    }
+  }
+  Ok(())
+}
+
+/// Fixes tier roles of everyone who has points, use after changing tiers
+#[command]
+async fn resync(ctx: &Context, msg: &Message) -> CommandResult {
+  if let Some(guild_id) = msg.guild_id {
+    let updated = tiers::resync(ctx, &guild_id).await?;
+    let out = format!("Tier roles resynced, {} members updated", updated);
+    channel_message(ctx, msg, out.as_str()).await;
+  }
+  Ok(())
+}
+
+/// transfers bigger than that are marked in audit
+static SUSPICIOUS_TRANSFER : i64 = 100;
+
+/// `~audit [@user]` shows recent transfers and other notable points changes
+#[command]
+async fn audit(ctx: &Context, msg: &Message) -> CommandResult {
+  if let Some(guild_id) = msg.guild_id {
+    let mut entries = ledger::audit_log(guild_id.as_u64().clone()).await?;
+    if msg.mentions.len() > 0 {
+      let target = msg.mentions[0].id.as_u64().clone();
+      entries.retain(|e| e.user == target || e.counterparty == target);
+    }
+    let description = if entries.is_empty() {
+        String::from("nothing to audit")
+      } else {
+        entries.iter().take(20).map(|e| {
+          let line = if e.counterparty != 0 {
+              format!("`{}` {} → {} **{}**", e.date(), UserId(e.user).mention()
+                                            , UserId(e.counterparty).mention(), -e.delta)
+            } else {
+              format!("`{}` {} **{:+}** {}", e.date(), UserId(e.user).mention(), e.delta, e.reason)
+            };
+          if e.delta.abs() >= SUSPICIOUS_TRANSFER && e.counterparty != 0 {
+            format!("⚠️ {}", line)
+          } else { line }
+        }).collect::<Vec<String>>().join("\n")
+      };
+    let footer = format!("Requested by {}", msg.author.name);
+    if let Err(why) = msg.channel_id.send_message(ctx, |m| m
+      .embed(|e| e
+      .title("Points audit")
+      .description(description)
+      .footer(|f| f.text(footer))
+    )).await {
+      error!("Failed to post audit {:?}", why);
+    }
+  }
+  Ok(())
+}
+
+/// `~pointrules` shows what messages give chat points
+/// `~pointrules set <rule> <value>` changes one rule
+/// `~pointrules reset` goes back to defaults
+#[command]
+async fn pointrules(ctx: &Context, msg: &Message, mut args : Args) -> CommandResult {
+  if let Some(guild_id) = msg.guild_id {
+    let guild_u64 = guild_id.as_u64().clone();
+    let mut rules = antifarm::get_rules(guild_u64).await?;
+    if let Ok(action) = args.single::<String>() {
+      match action.as_str() {
+        "set" => {
+          set!{ rule  = args.single::<String>().unwrap_or_default()
+              , value = args.single::<String>().unwrap_or_default() };
+          if rules.set(rule.as_str(), value.as_str()) {
+            antifarm::set_rules(guild_u64, rules.clone()).await?;
+          } else {
+            channel_message(ctx, msg,
+              "usage: pointrules set <cooldown|min_length|duplicates|attachments> <value>").await;
+            return Ok(());
+          }
+        },
+        "reset" => {
+          rules = antifarm::Rules::default();
+          antifarm::set_rules(guild_u64, rules.clone()).await?;
+        },
+        _ => {
+          channel_message(ctx, msg, "point rules could be only set or reset").await;
+          return Ok(());
+        }
+      }
+    }
+    let on_off = |b: bool| if b { "on" } else { "off" };
+    if let Err(why) = msg.channel_id.send_message(ctx, |m| m
+      .embed(|e| e
+      .title("Chat points rules")
+      .description("one point per qualifying message")
+      .field("cooldown", format!("{} seconds", rules.cooldown), true)
+      .field("min_length", format!("{} letters", rules.min_length), true)
+      .field("duplicates", on_off(rules.duplicates), true)
+      .field("attachments", on_off(rules.attachments), true)
+    )).await {
+      error!("Failed to post point rules {:?}", why);
+    }
+  }
+  Ok(())
+}
+
+/// Mentioned members and members of mentioned roles
+async fn targets(ctx: &Context, msg: &Message) -> Vec<u64> {
+  let mut users: Vec<u64> = msg.mentions.iter().map(|u| u.id.as_u64().clone()).collect();
+  if !msg.mention_roles.is_empty() {
+    if let Some(guild) = msg.guild(ctx).await {
+      for (user_id, member) in guild.members.iter() {
+        if msg.mention_roles.iter().any(|r| member.roles.contains(r)) {
+          users.push(user_id.as_u64().clone());
+        }
+      }
+    }
+  }
+  users.sort();
+  users.dedup();
+  users
+}
+
+/// shared part of grant, revoke, setpoints and reset
+/// args are `[points] <@user|@role>... <reason>`
+async fn manage_points( ctx: &Context, msg: &Message, mut args: Args
+                      , make: fn(u64) -> points::Adjustment
+                      , with_count: bool, usage: &str ) -> CommandResult {
+  if let Some(guild_id) = msg.guild_id {
+    let count = if with_count { args.single::<u64>().ok() } else { Some(0) };
+    // mentions are parsed by Discord, here they are just skipped to get reason
+    while args.current().map(|a| a.starts_with("<@")).unwrap_or(false) {
+      args.advance();
+    }
+    set!{ reason = args.rest().trim().to_string()
+        , users  = targets(ctx, msg).await };
+    let count = match count {
+      Some(count) if !users.is_empty() && !reason.is_empty() => count,
+      _ => {
+        channel_message(ctx, msg, usage).await;
+        return Ok(());
+      }
+    };
+    let adjustment = make(count);
+    let (adjusted, changes) =
+      points::adjust_points( guild_id.as_u64().clone(), users
+                           , adjustment, msg.author.id.as_u64().clone(), reason.clone() ).await?;
+    tiers::apply_all(ctx, &guild_id, &changes).await;
+    let members = adjusted.iter()
+                          .map(|a| format!("{} {} → {}", UserId(a.user).mention(), a.old, a.new))
+                          .collect::<Vec<String>>()
+                          .join(", ");
+    let text = format!("{} {} of {}\nreason: {}", msg.author.mention(), adjustment, members, reason);
+    log::log(ctx, &guild_id, text.as_str()).await;
+    let out = format!("Points of {} members updated", adjusted.len());
+    channel_message(ctx, msg, out.as_str()).await;
+  }
+  Ok(())
+}
+
+/// `~grant <points> <@user|@role>... <reason>`
+#[command]
+async fn grant(ctx: &Context, msg: &Message, args : Args) -> CommandResult {
+  manage_points(ctx, msg, args, points::Adjustment::Grant, true
+               , "usage: grant <points> <@user|@role> <reason>").await
+}
+
+/// `~revoke <points> <@user|@role>... <reason>`, points don't go below zero
+#[command]
+async fn revoke(ctx: &Context, msg: &Message, args : Args) -> CommandResult {
+  manage_points(ctx, msg, args, points::Adjustment::Revoke, true
+               , "usage: revoke <points> <@user|@role> <reason>").await
+}
+
+/// `~setpoints <points> <@user|@role>... <reason>`
+#[command]
+async fn setpoints(ctx: &Context, msg: &Message, args : Args) -> CommandResult {
+  manage_points(ctx, msg, args, points::Adjustment::Set, true
+               , "usage: setpoints <points> <@user|@role> <reason>").await
+}
+
+/// `~reset <@user|@role>... <reason>` removes points records completely
+#[command]
+async fn reset(ctx: &Context, msg: &Message, args : Args) -> CommandResult {
+  manage_points(ctx, msg, args, |_| points::Adjustment::Reset, false
+               , "usage: reset <@user|@role> <reason>").await
+}
+
+/// `~multiplier <#channel> <n>` sets chat points multiplier of channel
+/// 0 excludes channel, 1 is default
+#[command]
+async fn multiplier(ctx: &Context, msg: &Message, mut args : Args) -> CommandResult {
+  if let Some(guild_id) = msg.guild_id {
+    set!{ channel     = args.single::<ChannelId>()
+        , multiplier  = args.single::<u64>() };
+    if let (Ok(channel), Ok(multiplier)) = (channel, multiplier) {
+      multipliers::set_multiplier(guild_id.as_u64().clone(), channel.as_u64().clone(), multiplier).await?;
+      let out = match multiplier {
+        0 => format!("{} gives no points now", channel.mention()),
+        1 => format!("{} gives regular points now", channel.mention()),
+        m => format!("{} gives {}x points now", channel.mention(), m)
+      };
+      channel_message(ctx, msg, out.as_str()).await;
+    } else {
+      channel_message(ctx, msg, "usage: multiplier <#channel> <n>").await;
+    }
+  }
+  Ok(())
+}
+
+/// `~multipliers` lists channels with non default multipliers
+#[command]
+async fn multipliers(ctx: &Context, msg: &Message) -> CommandResult {
+  if let Some(guild_id) = msg.guild_id {
+    let channel_multipliers = multipliers::get_multipliers(guild_id.as_u64().clone()).await?;
+    let description = if channel_multipliers.is_empty() {
+        String::from("every channel gives regular points")
+      } else {
+        channel_multipliers.iter().map(|(channel, m)|
+          if *m == 0 {
+            format!("{}: excluded", ChannelId(*channel).mention())
+          } else {
+            format!("{}: **{}x**", ChannelId(*channel).mention(), m)
+          }
+        ).collect::<Vec<String>>().join("\n")
+      };
+    if let Err(why) = msg.channel_id.send_message(ctx, |m| m
+      .embed(|e| e
+      .title("Chat points multipliers")
+      .description(description)
+    )).await {
+      error!("Failed to post multipliers {:?}", why);
+    }
+  }
+  Ok(())
+}
+
+fn channels_list(channels: &[u64], defaults: &[&str]) -> String {
+  if channels.is_empty() {
+    format!("{} (by name)", defaults.join(", "))
+  } else {
+    channels.iter().map(|c| ChannelId(*c).mention()).collect::<Vec<String>>().join(", ")
+  }
+}
+
+fn bound_channel(channel: u64, name: String) -> String {
+  if channel == 0 { format!("{} (by name)", name) } else { ChannelId(channel).mention() }
+}
+
+/// channel mentions are not parsed by Discord into message so parsing them here
+/// only where channels are expected, plain numbers would pass as channel ids too
+fn mentioned_channels(args: &mut Args) -> Vec<u64> {
+  let mut channels = Vec::new();
+  while let Ok(channel) = args.single::<ChannelId>() {
+    channels.push(channel.as_u64().clone());
+  }
+  channels
+}
+
+/// `~config` shows guild settings
+/// `~config set ai.learn|ai.chat [#channel...]` sets channels, without channels goes back to defaults
+/// `~config set activity <n|default>` sets activity level of guild
+/// `~config bind log|main|digest <#channel>` and `~config unbind log|main|digest`
+#[command]
+async fn config(ctx: &Context, msg: &Message, mut args : Args) -> CommandResult {
+  if let Some(guild_id) = msg.guild_id {
+    let guild_u64 = guild_id.as_u64().clone();
+    set!{ action  = args.single::<String>().unwrap_or_default()
+        , key     = args.single::<String>().unwrap_or_default() };
+    let usage = "usage: config [set ai.learn|ai.chat #channel..., set activity <n|default>, bind|unbind log|main|digest #channel]";
+    let guild_settings = match (action.as_str(), key.as_str()) {
+      ("", _) => settings::get(guild_u64).await,
+      ("set", "ai.learn") => {
+        let channels = mentioned_channels(&mut args);
+        settings::update(guild_u64, move |s| s.ai_learn = channels).await?
+      },
+      ("set", "ai.chat") => {
+        let channels = mentioned_channels(&mut args);
+        settings::update(guild_u64, move |s| s.ai_chat = channels).await?
+      },
+      ("set", "activity") => {
+        let level = args.single::<String>().unwrap_or_default();
+        match level.parse::<u32>() {
+          Ok(n) if n > 0        => settings::update(guild_u64, move |s| s.activity = Some(n)).await?,
+          _ if level == "default" => settings::update(guild_u64, |s| s.activity = None).await?,
+          _ => {
+            channel_message(ctx, msg, "activity should be positive number or default").await;
+            return Ok(());
+          }
+        }
+      },
+      ("bind", "log") | ("bind", "main") | ("bind", "digest") => {
+        let channels = mentioned_channels(&mut args);
+        if channels.len() != 1 {
+          channel_message(ctx, msg, usage).await;
+          return Ok(());
+        }
+        let channel = channels[0];
+        match key.as_str() {
+          "log"  => settings::update(guild_u64, move |s| s.log = channel).await?,
+          "main" => settings::update(guild_u64, move |s| s.main = channel).await?,
+          _      => settings::update(guild_u64, move |s| s.digest = channel).await?
+        }
+      },
+      ("unbind", "log")    => settings::update(guild_u64, |s| s.log = 0).await?,
+      ("unbind", "main")   => settings::update(guild_u64, |s| s.main = 0).await?,
+      ("unbind", "digest") => settings::update(guild_u64, |s| s.digest = 0).await?,
+      _ => {
+        channel_message(ctx, msg, usage).await;
+        return Ok(());
+      }
+    };
+    let current = conf::current();
+    let activity = match guild_settings.activity {
+      Some(level) => level.to_string(),
+      None => format!("{} (global)", ACTIVITY_LEVEL.load(Ordering::Relaxed))
+    };
+    let digest = if guild_settings.digest == 0 && current.digest_channel.is_empty() {
+      String::from("not posted")
+    } else {
+      bound_channel(guild_settings.digest, current.digest_channel.clone())
+    };
+    if let Err(why) = msg.channel_id.send_message(ctx, |m| m
+      .embed(|e| e
+      .title("Guild settings")
+      .field("ai.learn", channels_list(&guild_settings.ai_learn, AI_LEARN), false)
+      .field("ai.chat", channels_list(&guild_settings.ai_chat, AI_ALLOWED), false)
+      .field("log", bound_channel(guild_settings.log, current.log_channel), true)
+      .field("main", bound_channel(guild_settings.main, current.main_channel), true)
+      .field("digest", digest, true)
+      .field("activity", activity, true)
+    )).await {
+      error!("Failed to post settings {:?}", why);
+    }
+  }
+  Ok(())
+}
+
+fn streams_line(player: &Player) -> String {
+  match &player.streams {
+    Some(Streams { twitch, ggru }) => {
+      let mut out = Vec::new();
+      if let Some(t) = twitch { out.push(format!("twitch: {}", t)); }
+      if let Some(g) = ggru { out.push(format!("ggru: {}", g)); }
+      out.join(", ")
+    },
+    None => String::new()
+  }
+}
+
+/// sets or clears (with `none`) stream handle of player
+fn set_stream(player: &mut Player, twitch: bool, value: &str) {
+  let handle = if value == "none" { None } else { Some(Cow::Owned(value.to_string())) };
+  let mut streams = player.streams.take().unwrap_or(Streams { ggru: None, twitch: None });
+  if twitch { streams.twitch = handle; } else { streams.ggru = handle; }
+  if streams.twitch.is_some() || streams.ggru.is_some() {
+    player.streams = Some(streams);
+  }
+}
+
+/// `~roster` lists tracked players
+/// `~roster add <battletag> <@user> [division] [--gw us|eu|asia]` and `~roster remove <battletag>`
+/// `~roster move <battletag> <1|2|interesting>`
+/// `~roster edit <battletag> twitch|ggru|battletag <value|none>` or `~roster edit <battletag> discord <@user>`
+/// `~roster edit <battletag> gateway <us|eu|asia|default>`
+#[command]
+async fn roster(ctx: &Context, msg: &Message, mut args : Args) -> CommandResult {
+  set!{ action    = args.single::<String>().unwrap_or_default()
+      , battletag = args.single::<String>().unwrap_or_default()
+      , mentioned = msg.mentions.first().map(|u| u.id.as_u64().clone()) };
+  let usage = "usage: roster [add <battletag> <@user> [division] [--gw us|eu|asia], remove <battletag>, move <battletag> <division>, edit <battletag> <twitch|ggru|discord|battletag|gateway> <value>]";
+  if !action.is_empty() && battletag.is_empty() {
+    channel_message(ctx, msg, usage).await;
+    return Ok(());
+  }
+  let changed = match action.as_str() {
+    "" => Ok(String::new()),
+    "add" => {
+      // mention is first argument left after battletag
+      let _ = args.single::<String>();
+      setm!{ division = Division::Interesting
+           , gateway  = 0 };
+      while let Ok(arg) = args.single::<String>() {
+        if arg == "--gw" {
+          match args.single::<String>().ok().and_then(|g| get_gateway(g.as_str())) {
+            Some(gw) => gateway = gw,
+            None => {
+              channel_message(ctx, msg, "gateway should be us, eu or asia").await;
+              return Ok(());
+            }
+          }
+        } else if let Ok(d) = arg.parse::<Division>() {
+          division = d;
+        }
+      }
+      match mentioned {
+        Some(discord) => roster::update(move |members| {
+          if members.iter().any(|m| m.is(&battletag)) {
+            return Err(format!("{} is already in roster", battletag));
+          }
+          members.push(Member { division: division
+                              , player: Player { battletag: Cow::Owned(battletag.clone())
+                                               , discord: discord
+                                               , streams: None
+                                               , gateway: gateway } });
+          Ok(format!("{} added to {}", battletag, division))
+        }).await?,
+        None => Err(String::from(usage))
+      }
+    },
+    "remove" => roster::update(move |members| {
+      let before = members.len();
+      members.retain(|m| !m.is(&battletag));
+      if members.len() == before {
+        Err(format!("{} is not in roster", battletag))
+      } else {
+        Ok(format!("{} removed", battletag))
+      }
+    }).await?,
+    "move" => match args.single::<String>().ok().and_then(|d| d.parse::<Division>().ok()) {
+      Some(division) => roster::update(move |members| {
+        match members.iter_mut().find(|m| m.is(&battletag)) {
+          Some(member) => {
+            member.division = division;
+            Ok(format!("{} moved to {}", battletag, division))
+          },
+          None => Err(format!("{} is not in roster", battletag))
+        }
+      }).await?,
+      None => Err(String::from("division should be 1, 2 or interesting"))
+    },
+    "edit" => {
+      set!{ field = args.single::<String>().unwrap_or_default()
+          , value = args.single::<String>().unwrap_or_default() };
+      if value.is_empty() {
+        Err(String::from(usage))
+      } else {
+        roster::update(move |members| {
+          let member = match members.iter_mut().find(|m| m.is(&battletag)) {
+            Some(member) => member,
+            None => return Err(format!("{} is not in roster", battletag))
+          };
+          match field.as_str() {
+            "twitch"    => set_stream(&mut member.player, true, value.as_str()),
+            "ggru"      => set_stream(&mut member.player, false, value.as_str()),
+            "battletag" => member.player.battletag = Cow::Owned(value.clone()),
+            "gateway"   => match get_gateway(value.as_str()) {
+              Some(gateway) => member.player.gateway = gateway,
+              None if value == "default" => member.player.gateway = 0,
+              None => return Err(String::from("gateway should be us, eu, asia or default"))
+            },
+            "discord"   => match mentioned {
+              Some(discord) => member.player.discord = discord,
+              None => return Err(String::from("discord should be user mention"))
+            },
+            _ => return Err(String::from(usage))
+          }
+          Ok(format!("{} of {} changed", field, battletag))
+        }).await?
+      }
+    },
+    _ => Err(String::from(usage))
+  };
+  match changed {
+    Ok(note) if !note.is_empty() => {
+      info!("roster: {}", note);
+      channel_message(ctx, msg, note.as_str()).await;
+    },
+    Ok(_) => (),
+    Err(why) => {
+      channel_message(ctx, msg, why.as_str()).await;
+      return Ok(());
+    }
+  }
+  let members = roster::members().await?;
+  let list = |division: Division| {
+    let lines = members.iter().filter(|m| m.division == division).map(|m| {
+      let mut streams = streams_line(&m.player);
+      if m.player.gateway != 0 {
+        if !streams.is_empty() { streams.push_str(", "); }
+        streams.push_str(&get_gateway_name(m.player.gateway));
+      }
+      if streams.is_empty() {
+        format!("{} {}", m.player.battletag, UserId(m.player.discord).mention())
+      } else {
+        format!("{} {} ({})", m.player.battletag, UserId(m.player.discord).mention(), streams)
+      }
+    }).collect::<Vec<String>>();
+    if lines.is_empty() { String::from("nobody") } else { lines.join("\n") }
+  };
+  if let Err(why) = msg.channel_id.send_message(ctx, |m| m
+    .embed(|e| e
+    .title("Roster")
+    .field(Division::First, list(Division::First), false)
+    .field(Division::Second, list(Division::Second), false)
+    .field(Division::Interesting, list(Division::Interesting), false)
+  )).await {
+    error!("Failed to post roster {:?}", why);
+  }
+  Ok(())
+}
+
+/// `~approve` lists battletags waiting for approval
+/// `~approve @user` adds linked battletag to roster, `~approve deny @user` removes link
+#[command]
+async fn approve(ctx: &Context, msg: &Message, mut args : Args) -> CommandResult {
+  let deny = args.single::<String>().map(|a| a == "deny").unwrap_or(false);
+  if let Some(user) = msg.mentions.first() {
+    let user_u64 = user.id.as_u64().clone();
+    let resp = if deny {
+        match links::unlink(user_u64).await? {
+          Some(link) => format!("link of {} to {} is removed", user.name, link.battletag),
+          None => format!("{} has no linked battletag", user.name)
+        }
+      } else {
+        match links::approve(user_u64).await? {
+          Some(battletag) => {
+            info!("roster: {} approved for {}", battletag, user.name);
+            format!("{} of {} approved and added to roster", battletag, user.name)
+          },
+          None => format!("{} has no linked battletag", user.name)
+        }
+      };
+    channel_message(ctx, msg, resp.as_str()).await;
+  } else {
+    let pending = links::pending().await?;
+    let description = if pending.is_empty() {
+        String::from("nothing to approve")
+      } else {
+        pending.iter()
+               .map(|(user, link)| format!("{} {}", UserId(*user).mention(), link.battletag))
+               .collect::<Vec<String>>()
+               .join("\n")
+      };
+    if let Err(why) = msg.channel_id.send_message(ctx, |m| m
+      .embed(|e| e
+      .title("Battletags waiting for approval")
+      .description(description)
+    )).await {
+      error!("Failed to post pending links {:?}", why);
+    }
+  }
+  Ok(())
+}
@@ -1,6 +1,6 @@
 use crate::{
   common::{
-    points,
+    points, tiers, ledger,
     msg::{ channel_message }
   },
   stains::ai::chain
@@ -8,7 +8,7 @@ use crate::{
 
 use serenity::{
   prelude::*,
-  model::channel::*,
+  model::{ channel::*, id::UserId, misc::Mentionable },
   framework::standard::{
     CommandResult, Args,
     macros::command
@@ -63,22 +63,25 @@ async fn give(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
             0
           };
         if points_count > 0 {
-          let (succ, rst) = points::give_points( guild.id.as_u64().clone()
-                                               , msg.author.id.as_u64().clone()
-                                               , target_user.id.as_u64().clone()
-                                               , points_count).await;
-          if succ {
-            let out = format!("{} to {}", rst, target_user.name);
-            let footer = format!("{}", msg.author.name);
-            if let Err(why) = msg.channel_id.send_message(ctx, |m| m
-              .embed(|e| e
-              .description(out.as_str())
-              .footer(|f| f.text(footer))
-            )).await {
-              error!("Failed to post give {:?}", why);
+          match points::give_points( guild.id.as_u64().clone()
+                                   , msg.author.id.as_u64().clone()
+                                   , target_user.id.as_u64().clone()
+                                   , points_count).await {
+            Ok(changes) => {
+              tiers::apply_all(ctx, &guild.id, &changes).await;
+              let out = format!("{} points transfered to {}", points_count, target_user.name);
+              let footer = format!("{}", msg.author.name);
+              if let Err(why) = msg.channel_id.send_message(ctx, |m| m
+                .embed(|e| e
+                .description(out.as_str())
+                .footer(|f| f.text(footer))
+              )).await {
+                error!("Failed to post give {:?}", why);
+              }
+            },
+            Err(why) => {
+              channel_message(ctx, msg, why.to_string().as_str()).await;
             }
-          } else {
-            channel_message(ctx, msg, rst.as_str()).await;
           }
         }
       }
@@ -89,6 +92,82 @@ async fn give(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
   Ok(())
 }
 
+#[command]
+#[aliases(leaderboard)]
+async fn top(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+  if let Some(guild) = msg.guild(&ctx).await {
+    set!{ page_size = args.single::<usize>().unwrap_or(10).max(1).min(25)
+        , page      = args.single::<usize>().unwrap_or(1).max(1)
+        , top       = points::get_top(guild.id.as_u64().clone()).await? };
+    if top.is_empty() {
+      channel_message(ctx, msg, "nobody has any points here yet").await;
+      return Ok(());
+    }
+    set!{ pages = (top.len() + page_size - 1) / page_size
+        , page  = page.min(pages) };
+    let mut description = String::new();
+    for (i, (user_id, count)) in top.iter().enumerate()
+                                    .skip((page - 1) * page_size)
+                                    .take(page_size) {
+      // only visible page is resolved so big guilds don't spam http
+      let name =
+        if let Some(member) = guild.members.get(&UserId(*user_id)) {
+          member.display_name().to_string()
+        } else if let Ok(user) = ctx.http.get_user(*user_id).await {
+          user.name
+        } else {
+          user_id.to_string()
+        };
+      description = format!("{}**{}.** {} : {}\n", description, i + 1, name, count);
+    }
+    let own_rank = top.iter().position(|(u, _)| u == msg.author.id.as_u64());
+    let footer = match own_rank {
+      Some(rank) => format!("Page {}/{} • {} is #{} with {} points"
+                           , page, pages, msg.author.name, rank + 1, top[rank].1),
+      None       => format!("Page {}/{} • {} has no points yet"
+                           , page, pages, msg.author.name)
+    };
+    if let Err(why) = msg.channel_id.send_message(ctx, |m| m
+      .embed(|e| e
+      .title("Leaderboard")
+      .description(description)
+      .footer(|f| f.text(footer))
+    )).await {
+      error!("Failed to post leaderboard {:?}", why);
+    }
+  }
+  Ok(())
+}
+
+#[command]
+async fn history(ctx: &Context, msg: &Message) -> CommandResult {
+  if let Some(guild_id) = msg.guild_id {
+    let target = if msg.mentions.len() > 0 { &msg.mentions[0] } else { &msg.author };
+    let entries = ledger::history(guild_id.as_u64().clone(), target.id.as_u64().clone()).await?;
+    let description = if entries.is_empty() {
+        String::from("nothing happened yet")
+      } else {
+        entries.iter().take(15).map(|e| {
+          let with = if e.counterparty != 0 {
+              format!(" ({})", UserId(e.counterparty).mention())
+            } else { String::new() };
+          format!("`{}` **{:+}** {}{}", e.date(), e.delta, e.reason, with)
+        }).collect::<Vec<String>>().join("\n")
+      };
+    let footer = format!("Requested by {}", msg.author.name);
+    if let Err(why) = msg.channel_id.send_message(ctx, |m| m
+      .embed(|e| e
+      .author(|a| a.icon_url(&target.face()).name(&target.name))
+      .title("Points history")
+      .description(description)
+      .footer(|f| f.text(footer))
+    )).await {
+      error!("Failed to post history for {}, {:?}", target.name, why);
+    }
+  }
+  Ok(())
+}
+
 #[command]
 async fn quote(ctx: &Context, msg: &Message) -> CommandResult {
   if msg.mentions.len() > 0 {
@@ -51,6 +51,8 @@ to execute commands use `~*<command>*` or `@Amadeus *<command>*`, replace *<thin
 • **quote** *<@user>*: something from that *user*
 • **score** *<@user>*: show *user* points
 • **give** *<@user>* *<N>*: give *user* your *N* points
+• **top** *<N>* *<page>*: points leaderboard, *N* places per page
+• **history** *<@user>*: recent points changes of *user*
 • **embed** *<title>* *<description>*: create embed
 • **qrcode** *<something>*: creates QR code
 • **urban** *<thing>*: explains a thing
@@ -1,5 +1,7 @@
 use crate::{
   common::{
+    antifarm, backup, conf, multipliers, settings, tiers,
+    state::{ self, Slot },
     msg::{ channel_message, direct_message }
   },
   stains::gate,
@@ -10,13 +12,46 @@ use serenity::{
   model::{ id::ChannelId
          , channel::* },
   prelude::*,
+  http::AttachmentType,
   framework::standard::{
     Args, CommandResult,
     macros::command
   }
 };
 
-use std::sync::atomic::{ Ordering };
+use std::{
+  borrow::Cow,
+  sync::atomic::{ Ordering }
+};
+
+/// Reads config again and applies what could be applied without restart
+pub async fn reload_config() -> Result<conf::Reloaded, conf::Error> {
+  let (options, reloaded) = conf::reload()?;
+  if reloaded.applied.contains(&"AI.activity") {
+    ACTIVITY_LEVEL.store(options.activity, Ordering::Relaxed);
+    if let Err(why) = state::store(Slot::Activity, options.activity).await {
+      error!("Failed to save activity level {:?}", why);
+    }
+  }
+  settings::forget_cached();
+  antifarm::forget_cached();
+  multipliers::forget_cached();
+  Ok(reloaded)
+}
+
+/// `~reload` is the same as SIGHUP
+#[command]
+async fn reload(ctx: &Context, msg: &Message) -> CommandResult {
+  let out = match reload_config().await {
+    Ok(reloaded) => {
+      let keys = |k: &Vec<&str>| if k.is_empty() { String::from("nothing") } else { k.join(", ") };
+      format!("Config reloaded\napplied: {}\nneeds restart: {}", keys(&reloaded.applied), keys(&reloaded.restart))
+    },
+    Err(why) => format!("Config is not reloaded, {}", why)
+  };
+  channel_message(ctx, msg, out.as_str()).await;
+  Ok(())
+}
 
 #[command]
 async fn set(ctx: &Context, msg: &Message, mut args : Args) -> CommandResult {
@@ -28,6 +63,9 @@ async fn set(ctx: &Context, msg: &Message, mut args : Args) -> CommandResult {
       "activity" =>
         if let Ok(level) = args.single::<u32>() {
           ACTIVITY_LEVEL.store(level, Ordering::Relaxed);
+          if let Err(why) = state::store(Slot::Activity, level).await {
+            error!("Failed to save activity level {:?}", why);
+          }
           let chan_msg = format!("Activity level is: {} now", level);
           channel_message(&ctx, &msg, chan_msg.as_str()).await;
         },
@@ -96,3 +134,51 @@ async fn clear(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
   }
   Ok(())
 }
+
+/// `~backup [json|csv]` sends points of the guild in direct message
+#[command]
+async fn backup(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+  if let Some(guild_id) = msg.guild_id {
+    let format = args.single::<String>()
+                     .map(|f| f.parse::<backup::Format>())
+                     .unwrap_or(Ok(backup::Format::Json))?;
+    let text = backup::export(guild_id.as_u64().clone(), format).await?;
+    let file = AttachmentType::Bytes {
+      data: Cow::from(text.into_bytes()),
+      filename: format!("points_{}.{}", guild_id, format.extension())
+    };
+    if let Err(why) = msg.author.dm(ctx, |m| m.add_file(file)).await {
+      error!("Failed to send points backup {:?}", why);
+    }
+  }
+  Ok(())
+}
+
+/// `~restore [merge|overwrite]` with backup file attached
+/// merge only touches members from file, overwrite also clears everyone else
+#[command]
+async fn restore(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+  if let Some(guild_id) = msg.guild_id {
+    let overwrite = match args.single::<String>().unwrap_or_default().as_str() {
+      "overwrite" => true,
+      "merge" | "" => false,
+      _ => {
+        channel_message(ctx, msg, "restore could be merge or overwrite").await;
+        return Ok(());
+      }
+    };
+    if let Some(file) = msg.attachments.first() {
+      let bytes = file.download().await?;
+      let text = String::from_utf8_lossy(&bytes);
+      let format = backup::Format::from_file_name(file.filename.as_str());
+      let changed = backup::import(guild_id.as_u64().clone(), &text, format, overwrite).await?;
+      // tiers are recalculated on import so roles follow restored points
+      let updated = tiers::resync(ctx, &guild_id).await?;
+      let out = format!("Points restored, {} records changed, {} members got their tier role", changed, updated);
+      channel_message(ctx, msg, out.as_str()).await;
+    } else {
+      channel_message(ctx, msg, "attach points backup to restore from").await;
+    }
+  }
+  Ok(())
+}
@@ -1,10 +1,14 @@
 use crate::{
   common::{
+    conf, links,
+    roster::{ self, Division },
+    state::{ self, Slot },
     msg::{ channel_message }
   },
   stains::cyber::{
-    types::*,
-    utils::{ get_race, get_race2
+    types::*, w3c,
+    summary::{ finished_description, score_fields },
+    utils::{ get_race, get_race2, get_gateway, get_gateway_name
            , get_league, get_map, get_league_png }
   }
 };
@@ -19,9 +23,9 @@ use serenity::{
 };
 
 use std::collections::HashMap;
-use serde_json::Value;
 
-use reqwest;
+use futures_util::stream::{ self, StreamExt };
+
 use comfy_table::*;
 
 use std::{
@@ -32,13 +36,19 @@ use std::{
 pub static CURRENT_SEASON : AtomicU32 = AtomicU32::new(1);
 
 pub async fn update_current_season() {
-  if let Ok(res) = reqwest::get("https://statistic-service.w3champions.com/api/ladder/seasons").await {
-    if let Ok(seasons) = res.json::<Vec<Season>>().await {
+  match w3c::seasons().await {
+    Ok(seasons) => {
       let seasons_ids = seasons.iter().map(|s| s.id);
       if let Some(last_season) = seasons_ids.max() {
-        CURRENT_SEASON.store(last_season, Relaxed);
+        // remembered so it's right even if ladder is down after restart
+        if CURRENT_SEASON.swap(last_season, Relaxed) != last_season {
+          if let Err(why) = state::store(Slot::Season, last_season).await {
+            error!("Failed to save season {:?}", why);
+          }
+        }
       }
-    }
+    },
+    Err(why) => warn!("Failed to update season {}", why)
   }
 }
 
@@ -47,16 +57,41 @@ fn current_season() -> String {
   format!("{}", atom)
 }
 
+/// takes `--gw <us|eu|asia>` out of command text
+fn gateway_arg(text: &str) -> Result<(String, Option<u32>), &'static str> {
+  let mut rest = Vec::new();
+  let mut gateway = None;
+  let mut words = text.split_whitespace();
+  while let Some(word) = words.next() {
+    if word == "--gw" {
+      match words.next().and_then(get_gateway) {
+        Some(gw) => gateway = Some(gw),
+        None => return Err("gateway should be us, eu or asia")
+      }
+    } else {
+      rest.push(word);
+    }
+  }
+  Ok((rest.join(" "), gateway))
+}
+
+/// `~ongoing [--gw us|eu|asia]`
 #[command]
-async fn ongoing(ctx: &Context, msg: &Message) -> CommandResult {
+async fn ongoing(ctx: &Context, msg: &Message, args : Args) -> CommandResult {
+  let gateway = match gateway_arg(args.message()) {
+    Ok((_, gateway)) => gateway.unwrap_or(conf::current().gateway),
+    Err(why) => {
+      channel_message(&ctx, &msg, why).await;
+      return Ok(());
+    }
+  };
   if let Err(why) = msg.delete(&ctx).await {
     error!("Error deleting original command {:?}", why);
   }
-  let url = format!("https://statistic-service.w3champions.com/api/matches/ongoing?offset=0&gateway=20&gameMode={}", current_season());
-  let res = reqwest::get(url.as_str()).await?;
-  let going : Going = res.json().await?;
+  // only 1v1 fits in one line
+  let going = w3c::ongoing(gateway, Some(1)).await?;
   if going.matches.len() > 0 {
-    let footer = format!("Requested by {}", msg.author.name);
+    let footer = format!("{}, requested by {}", get_gateway_name(gateway), msg.author.name);
     let mut description : String = String:: new();
     for m in going.matches.into_iter().take(15).collect::<Vec<Match>>() {
       if m.teams.len() > 1 && m.teams[0].players.len() > 0 && m.teams[1].players.len() > 0 {
@@ -84,29 +119,217 @@ async fn ongoing(ctx: &Context, msg: &Message) -> CommandResult {
   Ok(())
 }
 
+/// `~match <id>` shows summary of finished match, id is the last part of w3champions match link
+#[command("match")]
+async fn match_summary(ctx: &Context, msg: &Message, args : Args) -> CommandResult {
+  let match_id = args.message().trim().trim_end_matches('/').rsplit('/').next().unwrap_or("").to_string();
+  if match_id.is_empty() {
+    channel_message(&ctx, &msg, "usage: match <id>").await;
+    return Ok(());
+  }
+  let md = match w3c::match_details(match_id.as_str()).await {
+    Ok(md) => md,
+    Err(w3c::Error::Status(404, _)) => {
+      let resp = format!("Match {} not found", match_id);
+      channel_message(&ctx, &msg, resp.as_str()).await;
+      return Ok(());
+    },
+    Err(why) => return Err(why.into())
+  };
+  set!{ description = finished_description(&md.match_data)
+      , fields      = score_fields(&md.match_data, &md.playerScores)
+      , footer      = format!("Passed: {} min, requested by {}", md.match_data.durationInSeconds / 60, msg.author.name)
+      , url         = format!("https://www.w3champions.com/match/{}", match_id) };
+  if let Err(why) = msg.channel_id.send_message(&ctx, |m| m
+    .embed(|e| {
+      let mut e = e
+        .title("FINISHED")
+        .description(description)
+        .url(url)
+        .colour((180,40,200))
+        .footer(|f| f.text(footer));
+      if fields.len() > 0 {
+        e = e.fields(fields);
+      }
+      e
+    })).await {
+    error!("Error sending match message: {:?}", why);
+  }
+  Ok(())
+}
+
+/// requests to w3champions at once while collecting standings
+static STANDINGS_REQUESTS : usize = 4;
+
+/// solo stats of player this season, `None` if there are no games
+async fn solo_stats(battletag: String, season: String, gateway: u32) -> (String, Option<GMStats>) {
+  let stats = match w3c::game_mode_stats(battletag.as_str(), season.as_str(), gateway).await {
+    Ok(stats) => stats.into_iter().find(|s| s.gameMode == 1),
+    Err(why) => {
+      warn!("Failed to get stats of {} {}", battletag, why);
+      None
+    }
+  };
+  (battletag, stats)
+}
+
+fn standings_table(mut rows: Vec<(String, GMStats)>) -> String {
+  rows.sort_by(|(_, a), (_, b)| b.mmr.cmp(&a.mmr));
+  let mut table = Table::new();
+  table.load_preset(presets::ASCII_MARKDOWN)
+       .set_content_arrangement(ContentArrangement::Dynamic)
+       .set_table_width(60)
+       .set_header(vec!["#", "Player", "League", "Win", "Games", "MMR", "+/-"]);
+  for (i, (battletag, s)) in rows.iter().enumerate() {
+    set!{ name    = battletag.split("#").next().unwrap_or(""),
+          league  = if s.games < 5 { String::from("Calibrating") }
+                    else if s.leagueOrder > 1 { format!("{} {}", get_league(s.leagueOrder), s.division) }
+                    else { get_league(s.leagueOrder) },
+          winrate = format!("{}%", (s.winrate * 100.0).round()),
+          change  = format!("{:+}", s.rankingPointsProgress.mmr) };
+    table.add_row(vec![
+      Cell::new(i + 1),
+      Cell::new(name),
+      Cell::new(league),
+      Cell::new(winrate).set_alignment(CellAlignment::Right),
+      Cell::new(s.games).set_alignment(CellAlignment::Right),
+      Cell::new(s.mmr).set_alignment(CellAlignment::Right),
+      Cell::new(change).set_alignment(CellAlignment::Right)
+    ]);
+  }
+  format!("```\n{}\n```", table)
+}
+
+/// `~standings [1|2|interesting]` ranks roster players by solo MMR this season
 #[command]
-async fn stats(ctx: &Context, msg: &Message, args : Args) -> CommandResult {
-  let mut args_msg = args.message();
-  if args_msg.is_empty() {
-    args_msg = msg.author.name.as_str();
+async fn standings(ctx: &Context, msg: &Message, args : Args) -> CommandResult {
+  let division_arg = args.message().trim();
+  let divisions = if division_arg.is_empty() {
+      vec![Division::First, Division::Second, Division::Interesting]
+    } else if let Ok(division) = division_arg.parse::<Division>() {
+      vec![division]
+    } else {
+      channel_message(&ctx, &msg, "usage: standings [1|2|interesting]").await;
+      return Ok(());
+    };
+  let members = roster::members().await?;
+  let season = current_season();
+  for division in divisions {
+    let players = members.iter()
+                         .filter(|m| m.division == division)
+                         .map(|m| (m.player.battletag.to_string(), m.player.gateway()))
+                         .collect::<Vec<(String, u32)>>();
+    if players.is_empty() {
+      continue;
+    }
+    let all = players.len();
+    let rows = stream::iter(players)
+      .map(|(p, gw)| solo_stats(p, season.clone(), gw))
+      .buffer_unordered(STANDINGS_REQUESTS)
+      .filter_map(|(p, s)| async move { s.map(|s| (p, s)) })
+      .collect::<Vec<(String, GMStats)>>().await;
+    let footer = if rows.len() < all {
+        format!("Season {}, {} players without solo games", season, all - rows.len())
+      } else {
+        format!("Season {}", season)
+      };
+    let description = if rows.is_empty() {
+        String::from("nobody played solo this season")
+      } else {
+        standings_table(rows)
+      };
+    if let Err(why) = msg.channel_id.send_message(&ctx, |m| m
+      .embed(|e| e
+        .title(format!("{} standings", division))
+        .description(description)
+        .colour((180,40,200))
+        .footer(|f| f.text(footer)))).await {
+      error!("Error sending standings message: {:?}", why);
+    }
+  }
+  Ok(())
+}
+
+/// battletags of ladder players found by name
+async fn search_battletags(name: &str, season: &str, gateway: u32) -> Result<Vec<String>, w3c::Error> {
+  let search = w3c::search(name, season, gateway).await?;
+  Ok(search.into_iter()
+           .flat_map(|s| s.player.playerIds.into_iter().map(|p| p.battleTag))
+           .collect())
+}
+
+/// `~link <BattleTag#1234> [--gw us|eu|asia]` links your battletag, admins approve it with `~approve`
+#[command]
+async fn link(ctx: &Context, msg: &Message, args : Args) -> CommandResult {
+  let (battletag, gw) = match gateway_arg(args.message()) {
+    Ok(parsed) => parsed,
+    Err(why) => {
+      channel_message(&ctx, &msg, why).await;
+      return Ok(());
+    }
+  };
+  if !battletag.contains("#") {
+    channel_message(&ctx, &msg, "usage: link <BattleTag#1234> [--gw us|eu|asia]").await;
+    return Ok(());
   }
+  let gateway = gw.unwrap_or(conf::current().gateway);
+  let name = battletag.split("#").next().unwrap_or("");
+  let found = search_battletags(name, current_season().as_str(), gateway).await?
+                .into_iter()
+                .find(|tag| tag.to_lowercase() == battletag.to_lowercase());
+  let resp = match found {
+    Some(tag) => {
+      // without --gw link follows configured gateway
+      links::link(msg.author.id.as_u64().clone(), tag.clone(), gw.unwrap_or(0)).await?;
+      format!("{} linked, it will be tracked after admin approves it", tag)
+    },
+    None => format!("{} is not found on {} ladder", battletag, get_gateway_name(gateway))
+  };
+  channel_message(&ctx, &msg, resp.as_str()).await;
+  Ok(())
+}
+
+#[command]
+async fn unlink(ctx: &Context, msg: &Message) -> CommandResult {
+  let resp = match links::unlink(msg.author.id.as_u64().clone()).await? {
+    Some(link) => format!("{} unlinked", link.battletag),
+    None => String::from("you have no linked battletag")
+  };
+  channel_message(&ctx, &msg, resp.as_str()).await;
+  Ok(())
+}
+
+/// `~stats [name or BattleTag#1234] [--gw us|eu|asia]`, linked battletag or your name by default
+#[command]
+async fn stats(ctx: &Context, msg: &Message, args : Args) -> CommandResult {
+  let (name, gw) = match gateway_arg(args.message()) {
+    Ok(parsed) => parsed,
+    Err(why) => {
+      channel_message(&ctx, &msg, why).await;
+      return Ok(());
+    }
+  };
+  let default_gateway = conf::current().gateway;
+  let (args_msg, gateway) = if !name.is_empty() {
+      (name, gw.unwrap_or(default_gateway))
+    } else {
+      match links::get(msg.author.id.as_u64().clone()).await? {
+        Some(link) => {
+          let linked_gateway = if link.gateway == 0 { default_gateway } else { link.gateway };
+          (link.battletag, gw.unwrap_or(linked_gateway))
+        },
+        None => (msg.author.name.clone(), gw.unwrap_or(default_gateway))
+      }
+    };
+  let args_msg = args_msg.as_str();
   let season = current_season();
   let userx = if args_msg.contains("#") { String::from(args_msg) }
     else {
-      let search_uri = format!("https://statistic-service.w3champions.com/api/ladder/search?gateWay=20&searchFor={}&season={}", args_msg, season);
-      let ress = reqwest::get(search_uri.as_str()).await?;
-      let search : Vec<Search> = ress.json().await?;
-      if search.len() > 0 {
-        if search[0].player.playerIds.len() > 0 {
-          search[0].player.playerIds[0].battleTag.clone()
-        } else { String::from("") }
-      } else { String::from("") }
+      search_battletags(args_msg, season.as_str(), gateway).await?
+        .into_iter().next().unwrap_or_default()
     };
   if !userx.is_empty() {
-    let user = userx.replace("#","%23");
-    let game_mode_uri = format!("https://statistic-service.w3champions.com/api/players/{}/game-mode-stats?gateWay=20&season={}", user, season);
-    let game_mode_res = reqwest::get(game_mode_uri.as_str()).await?;
-    let game_mode_stats : Vec<GMStats> = game_mode_res.json().await?;
+    let game_mode_stats = w3c::game_mode_stats(userx.as_str(), season.as_str(), gateway).await?;
 
     setm!{ league_info         = String::new()
          , ffa_info            = String::new()
@@ -194,29 +417,20 @@ async fn stats(ctx: &Context, msg: &Message, args : Args) -> CommandResult {
       }
     }
 
-    let uri = format!("https://statistic-service.w3champions.com/api/players/{}/race-stats?gateWay=20&season={}", user, season);
-    let res = reqwest::get(uri.as_str()).await?;
-    let stats : Vec<Stats> = res.json().await?;
+    let stats = w3c::race_stats(userx.as_str(), season.as_str(), gateway).await?;
 
     let mut stats_by_races : String = String::new();
     if stats.len() > 0 {
 
-      let clan_uri = format!("https://statistic-service.w3champions.com/api/clans?battleTag={}", user);
       let name = &userx.split("#").collect::<Vec<&str>>()[0];
-      let mut clanned = String::from(*name);
-      if let Ok(clan_res) = reqwest::get(clan_uri.as_str()).await {
-        if let Ok(clan_text_res) = clan_res.text().await {
-          let clan_json_res = serde_json::from_str(clan_text_res.as_str());
-          if clan_json_res.is_ok() {
-            let clan_json : Value = clan_json_res.unwrap();
-            if let Some(clan) = clan_json.pointer("/clanId") {
-              if let Some(clan_str) = clan.as_str() {
-                clanned = format!("[{}] {}", clan_str, name);
-              }
-            }
-          }
+      let clanned = match w3c::clan(userx.as_str()).await {
+        Ok(Some(clan)) => format!("[{}] {}", clan, name),
+        Ok(None) => String::from(*name),
+        Err(why) => {
+          warn!("Failed to get clan of {} {}", userx, why);
+          String::from(*name)
         }
-      }
+      };
 
       for stat in &stats {
         let race = get_race(stat.race);
@@ -243,9 +457,7 @@ async fn stats(ctx: &Context, msg: &Message, args : Args) -> CommandResult {
 
       let mut description = format!("[{}] {}\n", userx.as_str(), league_info.as_str());
 
-      let uri2 = format!("https://statistic-service.w3champions.com/api/player-stats/{}/race-on-map-versus-race?season={}", user, season);
-      let res2 = reqwest::get(uri2.as_str()).await?;
-      let stats2 : Stats2 = res2.json().await?;
+      let stats2 = w3c::race_on_map(userx.as_str(), season.as_str()).await?;
 
       let mut table = Table::new();
 
@@ -1,8 +1,7 @@
 use crate::{
   common::{
-    types::AOptions,
     msg::{ direct_message, reply },
-    conf
+    state
   }
 };
 
@@ -27,22 +26,21 @@ impl TypeMapKey for VoiceManager {
   type Value = Arc<Mutex<ClientVoiceManager>>;
 }
 
-pub async fn rejoin_voice_channel(ctx : &Context, conf: &AOptions) {
-  if conf.rejoin {
-    set!{ last_guild_u64 = conf.last_guild.parse::<u64>().unwrap_or(0)
-        , last_channel_u64 = conf.last_channel.parse::<u64>().unwrap_or(0) };
-    if last_guild_u64 != 0 && last_channel_u64 != 0 {
-      set!{ last_guild_conf = GuildId( last_guild_u64 )
-          , last_channel_conf = ChannelId( last_channel_u64 ) };
+pub async fn rejoin_voice_channel(ctx : &Context) {
+  let voice_state = state::voice().await;
+  if voice_state.rejoin {
+    if voice_state.guild != 0 && voice_state.channel != 0 {
+      set!{ last_guild_conf = GuildId( voice_state.guild )
+          , last_channel_conf = ChannelId( voice_state.channel ) };
       let manager_lock =
         ctx.data.read().await
           .get::<VoiceManager>().cloned().expect("Expected VoiceManager in ShareMap.");
       let mut manager = manager_lock.lock().await;
       if manager.join(last_guild_conf, last_channel_conf).is_some() {
         info!("Rejoined voice channel: {}", last_channel_conf);
-        if conf.last_stream != "" {
+        if voice_state.stream != "" {
           if let Some(handler) = manager.get_mut(last_guild_conf) {
-            let source = match voice::ytdl(&conf.last_stream).await {
+            let source = match voice::ytdl(&voice_state.stream).await {
               Ok(source) => source,
               Err(why) => {
                 error!("Err starting source: {:?}", why);
@@ -83,15 +81,11 @@ async fn join(ctx: &Context, msg: &Message) -> CommandResult {
     .get::<VoiceManager>().cloned().expect("Expected VoiceManager in ShareMap.");
   let mut manager = manager_lock.lock().await;
   if manager.join(guild_id, connect_to).is_some() {
-    let mut conf = conf::parse_config();
-    let last_guild_conf = GuildId( conf.last_guild.parse::<u64>().unwrap_or(0) );
-    let last_channel_conf = ChannelId( conf.last_channel.parse::<u64>().unwrap_or(0) );
-    if last_guild_conf != guild_id || last_channel_conf != connect_to || conf.rejoin == false {
-      conf.rejoin = true;
-      conf.last_guild = format!("{}", guild_id);
-      conf.last_channel = format!("{}", connect_to);
-      conf::write_config(&conf);
-    }
+    state::update_voice(|v| {
+      v.rejoin = true;
+      v.guild = guild_id.as_u64().clone();
+      v.channel = connect_to.as_u64().clone();
+    }).await;
     if let Err(why) = msg.channel_id.say(&ctx, &format!("I've joined {}", connect_to.mention())).await {
       error!("failed to say joined {:?}", why);
     }
@@ -120,11 +114,7 @@ async fn leave(ctx: &Context, msg: &Message) -> CommandResult {
   if has_handler {
     manager.remove(guild_id);
     let _ = msg.channel_id.say(&ctx, "I left voice channel");
-    let mut conf = conf::parse_config();
-    if conf.rejoin {
-      conf.rejoin = false;
-      conf::write_config(&conf);
-    }
+    state::update_voice(|v| v.rejoin = false).await;
   } else {
     reply(ctx, &msg, "I'm not in a voice channel").await;
   }
@@ -146,8 +136,7 @@ async fn play(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
         }
       }
     } else {
-      let conf = conf::parse_config();
-      conf.last_stream
+      state::voice().await.stream
     };
   if !url.starts_with("http") {
     reply(ctx, msg, "You must provide a valid URL").await;
@@ -173,12 +162,8 @@ async fn play(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
       }
     };
     handler.play_only(source);
-    let mut conf = conf::parse_config();
-    let last_stream_conf = conf.last_stream;
-    if last_stream_conf != url {
-      conf.last_stream = url.clone();
-      conf::write_config(&conf);
-    }
+    let stream = url.clone();
+    state::update_voice(|v| v.stream = stream).await;
     reply(ctx, msg, &format!("playing stream: {}", url)).await;
   } else {
     reply(ctx, msg, "Not in a voice channel to play in...").await;
@@ -0,0 +1,150 @@
+/*
+* Anti-farming rules for chat points
+* rules are per guild in database, last message of every member is only in memory
+* so restart just forgets cooldowns
+*/
+
+use crate::common::db::{
+  self, DB, Record,
+  keys::{ Key, Table }
+};
+
+use std::{
+  collections::{ HashMap, hash_map::DefaultHasher },
+  hash::{ Hash, Hasher },
+  sync::{ Mutex, RwLock },
+  time::{ Duration, Instant }
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Rules {
+  /// seconds between two messages giving points
+  pub cooldown: u64,
+  /// letters and digits message must have
+  pub min_length: usize,
+  /// same message as previous one gives points
+  pub duplicates: bool,
+  /// message with only attachments gives points
+  pub attachments: bool
+}
+
+impl Default for Rules {
+  fn default() -> Rules {
+    Rules { cooldown: 30
+          , min_length: 3
+          , duplicates: false
+          , attachments: false }
+  }
+}
+
+impl Rules {
+  /// sets rule by name, returns `false` for unknown rule or bad value
+  pub fn set(&mut self, rule: &str, value: &str) -> bool {
+    let flag = |v: &str| match v {
+      "on" | "true" | "yes"  => Some(true),
+      "off" | "false" | "no" => Some(false),
+      _ => None
+    };
+    match rule {
+      "cooldown"    => value.parse().map(|v| self.cooldown = v).is_ok(),
+      "min_length"  => value.parse().map(|v| self.min_length = v).is_ok(),
+      "duplicates"  => flag(value).map(|v| self.duplicates = v).is_some(),
+      "attachments" => flag(value).map(|v| self.attachments = v).is_some(),
+      _ => false
+    }
+  }
+}
+
+impl Record for Rules {}
+
+struct Last {
+  /// last time member got points
+  rewarded: Instant,
+  /// hash of last message text
+  content: u64
+}
+
+// forget members silent for longer than that when there are too many of them
+static FORGET_AFTER : Duration = Duration::from_secs(60 * 60);
+static FORGET_ABOVE : usize = 10000;
+
+lazy_static! {
+  static ref LAST: Mutex<HashMap<(u64, u64), Last>> = Mutex::new(HashMap::new());
+  // read on every message so kept in memory after first load
+  static ref CACHE: RwLock<HashMap<u64, Rules>> = RwLock::new(HashMap::new());
+}
+
+pub async fn get_rules(guild_id: u64) -> Result<Rules, db::Error> {
+  if let Some(rules) = CACHE.read().ok().and_then(|c| c.get(&guild_id).cloned()) {
+    return Ok(rules);
+  }
+  let rules = DB.transaction(move |tx| -> Result<Rules, db::Error> {
+    Ok(tx.load(&Key::table(Table::PointRules, guild_id))?.unwrap_or_default())
+  }).await?;
+  if let Ok(mut cache) = CACHE.write() {
+    cache.insert(guild_id, rules.clone());
+  }
+  Ok(rules)
+}
+
+pub async fn set_rules(guild_id: u64, rules: Rules) -> Result<(), db::Error> {
+  let stored = rules.clone();
+  DB.transaction(move |tx| tx.store(Key::table(Table::PointRules, guild_id), &stored)).await?;
+  if let Ok(mut cache) = CACHE.write() {
+    cache.insert(guild_id, rules);
+  }
+  Ok(())
+}
+
+/// rules are read from database again on next use
+pub fn forget_cached() {
+  if let Ok(mut cache) = CACHE.write() {
+    cache.clear();
+  }
+}
+
+/// lower case words without spaces and punctuation
+fn normalize(content: &str) -> String {
+  content.chars()
+         .filter(|c| c.is_alphanumeric())
+         .flat_map(|c| c.to_lowercase())
+         .collect()
+}
+
+/// Checks message against guild rules and remembers it,
+/// only qualifying messages start new cooldown
+pub fn qualifies(rules: &Rules, guild_id: u64, user_id: u64, content: &str, has_attachments: bool) -> bool {
+  let text = normalize(content);
+  let hash = {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+  };
+  let now = Instant::now();
+  let mut last_messages = match LAST.lock() {
+    Ok(last) => last,
+    Err(_) => return false
+  };
+  if last_messages.len() > FORGET_ABOVE {
+    last_messages.retain(|_, l| now.duration_since(l.rewarded) < FORGET_AFTER);
+  }
+  let key = (guild_id, user_id);
+  let qualifying =
+    if text.is_empty() && has_attachments {
+      rules.attachments
+    } else {
+      text.chars().count() >= rules.min_length
+    };
+  let (cooled_down, duplicate) = match last_messages.get(&key) {
+    Some(last) => ( now.duration_since(last.rewarded) >= Duration::from_secs(rules.cooldown)
+                  , !text.is_empty() && last.content == hash ),
+    None => (true, false)
+  };
+  let rewarded = qualifying && cooled_down && (rules.duplicates || !duplicate);
+  if rewarded {
+    last_messages.insert(key, Last { rewarded: now, content: hash });
+  } else if let Some(last) = last_messages.get_mut(&key) {
+    last.content = hash;
+  }
+  rewarded
+}
@@ -0,0 +1,155 @@
+/*
+* Points export and import
+* JSON or CSV (user,count,role) so it could be edited or moved to another host
+*/
+
+use crate::common::points::{ self, Points };
+
+use std::{ fmt, fs, str::FromStr };
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+  Json,
+  Csv
+}
+
+impl FromStr for Format {
+  type Err = Error;
+  fn from_str(s: &str) -> Result<Format, Error> {
+    match s.to_lowercase().as_str() {
+      "json" => Ok(Format::Json),
+      "csv"  => Ok(Format::Csv),
+      other  => Err(Error::Format(other.to_string()))
+    }
+  }
+}
+
+impl Format {
+  /// guess format by file name, JSON is default
+  pub fn from_file_name(name: &str) -> Format {
+    if name.to_lowercase().ends_with(".csv") { Format::Csv } else { Format::Json }
+  }
+
+  pub fn extension(&self) -> &'static str {
+    match self {
+      Format::Json => "json",
+      Format::Csv  => "csv"
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Record {
+  pub user: u64,
+  pub count: u64,
+  pub role: u64
+}
+
+#[derive(Debug)]
+pub enum Error {
+  Format(String),
+  Json(serde_json::Error),
+  Csv(usize, String),
+  Io(std::io::Error),
+  Points(points::Error)
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::Format(s)       => write!(f, "unknown format {}, use json or csv", s),
+      Error::Json(why)       => write!(f, "bad json: {}", why),
+      Error::Csv(line, what) => write!(f, "bad csv on line {}: {}", line, what),
+      Error::Io(why)         => write!(f, "{}", why),
+      Error::Points(why)     => write!(f, "{}", why)
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl From<serde_json::Error> for Error {
+  fn from(why: serde_json::Error) -> Error { Error::Json(why) }
+}
+
+impl From<std::io::Error> for Error {
+  fn from(why: std::io::Error) -> Error { Error::Io(why) }
+}
+
+impl From<points::Error> for Error {
+  fn from(why: points::Error) -> Error { Error::Points(why) }
+}
+
+pub fn encode(records: &[Record], format: Format) -> Result<String, Error> {
+  match format {
+    Format::Json => Ok(serde_json::to_string_pretty(records)?),
+    Format::Csv  => {
+      let mut out = String::from("user,count,role\n");
+      for r in records {
+        out = format!("{}{},{},{}\n", out, r.user, r.count, r.role);
+      }
+      Ok(out)
+    }
+  }
+}
+
+pub fn decode(text: &str, format: Format) -> Result<Vec<Record>, Error> {
+  match format {
+    Format::Json => Ok(serde_json::from_str(text)?),
+    Format::Csv  => {
+      let mut records = Vec::new();
+      for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("user") {
+          continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        if fields.len() < 2 {
+          return Err(Error::Csv(i + 1, String::from("expected user,count,role")));
+        }
+        let parse = |s: &str| s.parse::<u64>().map_err(|why| Error::Csv(i + 1, why.to_string()));
+        records.push(Record { user: parse(fields[0])?
+                            , count: parse(fields[1])?
+                            , role: if fields.len() > 2 { parse(fields[2])? } else { 0 } });
+      }
+      Ok(records)
+    }
+  }
+}
+
+pub async fn export(guild_id: u64, format: Format) -> Result<String, Error> {
+  let records: Vec<Record> =
+    points::dump(guild_id).await?.into_iter()
+      .map(|(user, p)| Record { user: user, count: p.count, role: p.role })
+      .collect();
+  encode(&records, format)
+}
+
+/// returns how many records were changed
+pub async fn import(guild_id: u64, text: &str, format: Format, overwrite: bool) -> Result<usize, Error> {
+  let records = decode(text, format)?.into_iter()
+    .map(|r| (r.user, Points { count: r.count, role: r.role }))
+    .collect();
+  Ok(points::restore(guild_id, records, overwrite).await?)
+}
+
+/// offline export, empty path means stdout
+pub async fn export_to_file(guild_id: u64, format: Format, path: &str) -> Result<(), Error> {
+  let text = export(guild_id, format).await?;
+  if path.is_empty() {
+    print!("{}", text);
+  } else {
+    fs::write(path, text)?;
+    info!("points of {} exported to {}", guild_id, path);
+  }
+  Ok(())
+}
+
+pub async fn import_from_file(guild_id: u64, format: Format, path: &str, overwrite: bool) -> Result<(), Error> {
+  let text = fs::read_to_string(path)?;
+  let changed = import(guild_id, text.as_str(), format, overwrite).await?;
+  info!("{} points records of {} imported from {}", changed, guild_id, path);
+  // no Discord connection here
+  info!("tier roles on Discord are fixed with ~resync");
+  Ok(())
+}
@@ -1,52 +1,293 @@
-const CONF_FILE_NAME: &'static str = "conf.ini";
+/*
+* Configuration
+* conf.ini (or file given with --config) with defaults for everything but token,
+* secrets could be overridden with environment variables
+* file is only read, things changed at runtime are in `state`
+* could be reloaded on SIGHUP or with ~reload, some keys still need restart
+*/
 
-use crate::common::types;
+pub const CONF_FILE_NAME: &'static str = "conf.ini";
+
+use crate::common::{ types::AOptions, state };
 
 use ini::Ini;
 
-pub fn write_config(opts : &types::AOptions) {
+use std::{
+  env, fmt,
+  path::Path,
+  str::FromStr,
+  sync::RwLock
+};
+
+/// environment variable → (section, key) it overrides
+static ENV_OVERRIDES : &[(&str, &str, &str)] =
+  &[ ("AMADEUS_DISCORD_TOKEN",        "Discord", "token")
+   , ("AMADEUS_TWITCH_OAUTH",         "Twitch",  "oauth")
+   , ("AMADEUS_TWITCH_CLIENT_ID",     "Twitch",  "client_id")
+   , ("AMADEUS_TWITCH_CLIENT_SECRET", "Twitch",  "client_secret") ];
+
+/// w3champions gateways: America, Europe, Asia
+static GATEWAYS : &[u32] = &[10, 20, 30];
+
+#[derive(Debug)]
+pub enum Error {
+  Load(String, String),
+  Created(String),
+  Invalid { key: String, value: String, expected: &'static str }
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::Load(path, why) => write!(f, "failed to load {}: {}", path, why),
+      Error::Created(path)   => write!(f, "{} created with defaults, fill Discord token there", path),
+      Error::Invalid { key, value, expected } =>
+        write!(f, "bad value of {}: \"{}\", expected {}", key, value, expected)
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl Default for AOptions {
+  fn default() -> AOptions {
+    AOptions {
+      discord:              String::from(""),
+      prefix:               String::from("~"),
+      log_channel:          String::from("log"),
+      main_channel:         String::from("main"),
+      digest_channel:       String::from(""),
+      gateway:              20,
+      live_check_interval:  30,
+      w3c_api:              String::from("https://statistic-service.w3champions.com/api"),
+      w3c_timeout:          10,
+      w3c_retries:          2,
+      w3c_rate_limit:       5,
+      w3c_cache_ttl:        15,
+      chatter_interval:     30 * 60,
+      activity:             66,
+      guild:                0,
+      twitch_oauth:         String::from(""),
+      twitch_client_id:     String::from(""),
+      twitch_client_secret: String::from(""),
+      db_path:              String::from("tree.lusf"),
+      db_capacity:          666_666_666,
+      archive_days:         30
+    }
+  }
+}
+
+lazy_static! {
+  static ref CONF_PATH: RwLock<String> = RwLock::new(String::from(CONF_FILE_NAME));
+  static ref CURRENT: RwLock<AOptions> = RwLock::new(AOptions::default());
+}
+
+/// configuration loaded on start
+pub fn current() -> AOptions {
+  CURRENT.read().map(|c| c.clone()).unwrap_or_default()
+}
+
+/// replaces configuration without file, for tests
+#[cfg(test)]
+pub fn set_current(options: AOptions) {
+  if let Ok(mut current) = CURRENT.write() {
+    *current = options;
+  }
+}
+
+fn to_ini(opts: &AOptions) -> Ini {
   let mut conf = Ini::new();
   conf.with_section(None::<String>)
     .set("encoding", "utf-8");
   conf.with_section(Some("Discord".to_owned()))
-    .set("token", opts.discord.as_str());
-  conf.with_section(Some("Music".to_owned()))
-    .set("rejoin", if opts.rejoin { "true" } else { "false" })
-    .set("last_guild", opts.last_guild.as_str())
-    .set("last_channel", opts.last_channel.as_str())
-    .set("last_stream", opts.last_stream.as_str());
+    .set("token", opts.discord.as_str())
+    .set("prefix", opts.prefix.as_str())
+    .set("guild", opts.guild.to_string());
+  conf.with_section(Some("Channels".to_owned()))
+    .set("log", opts.log_channel.as_str())
+    .set("main", opts.main_channel.as_str())
+    .set("digest", opts.digest_channel.as_str());
+  conf.with_section(Some("W3C".to_owned()))
+    .set("gateway", opts.gateway.to_string())
+    .set("live_check_interval", opts.live_check_interval.to_string())
+    .set("api", opts.w3c_api.as_str())
+    .set("timeout", opts.w3c_timeout.to_string())
+    .set("retries", opts.w3c_retries.to_string())
+    .set("rate_limit", opts.w3c_rate_limit.to_string())
+    .set("cache_ttl", opts.w3c_cache_ttl.to_string());
+  conf.with_section(Some("AI".to_owned()))
+    .set("chatter_interval", opts.chatter_interval.to_string())
+    .set("activity", opts.activity.to_string());
   conf.with_section(Some("Twitch".to_owned()))
     .set("oauth", opts.twitch_oauth.as_str())
     .set("client_id", opts.twitch_client_id.as_str())
-    .set("client_secret", opts.twitch_client_id.as_str());
-  conf.write_to_file(CONF_FILE_NAME).unwrap();
-}
-
-pub fn parse_config() -> types::AOptions {
-  let mut options: types::AOptions = types::AOptions {
-    rejoin:               true,
-    discord:              String::from(""),
-    last_guild:           String::from(""),
-    last_channel:         String::from(""),
-    last_stream:          String::from(""),
-    twitch_oauth:         String::from(""),
-    twitch_client_id:     String::from(""),
-    twitch_client_secret: String::from("")
+    .set("client_secret", opts.twitch_client_secret.as_str());
+  conf.with_section(Some("Database".to_owned()))
+    .set("path", opts.db_path.as_str())
+    .set("capacity", opts.db_capacity.to_string());
+  conf.with_section(Some("Points".to_owned()))
+    .set("archive_days", opts.archive_days.to_string());
+  conf
+}
+
+fn string(conf: &Ini, section: &str, key: &str, default: &str) -> String {
+  conf.get_from(Some(section), key).unwrap_or(default).trim().to_string()
+}
+
+fn value<T: FromStr>( conf: &Ini, section: &str, key: &str
+                    , default: T, expected: &'static str ) -> Result<T, Error> {
+  match conf.get_from(Some(section), key) {
+    Some(v) => v.trim().parse::<T>().map_err(|_| Error::Invalid { key: format!("{}.{}", section, key)
+                                                              , value: v.to_string()
+                                                              , expected: expected }),
+    None => Ok(default)
+  }
+}
+
+fn check(ok: bool, key: &str, value: &str, expected: &'static str) -> Result<(), Error> {
+  if ok {
+    Ok(())
+  } else {
+    Err(Error::Invalid { key: key.to_string(), value: value.to_string(), expected: expected })
+  }
+}
+
+fn from_ini(conf: &Ini) -> Result<AOptions, Error> {
+  let d = AOptions::default();
+  let options = AOptions {
+    discord:              string(conf, "Discord", "token", &d.discord),
+    prefix:               string(conf, "Discord", "prefix", &d.prefix),
+    log_channel:          string(conf, "Channels", "log", &d.log_channel),
+    main_channel:         string(conf, "Channels", "main", &d.main_channel),
+    digest_channel:       string(conf, "Channels", "digest", &d.digest_channel),
+    gateway:              value(conf, "W3C", "gateway", d.gateway, "10, 20 or 30")?,
+    live_check_interval:  value(conf, "W3C", "live_check_interval", d.live_check_interval, "seconds")?,
+    w3c_api:              string(conf, "W3C", "api", &d.w3c_api).trim_end_matches('/').to_string(),
+    w3c_timeout:          value(conf, "W3C", "timeout", d.w3c_timeout, "seconds")?,
+    w3c_retries:          value(conf, "W3C", "retries", d.w3c_retries, "number of retries")?,
+    w3c_rate_limit:       value(conf, "W3C", "rate_limit", d.w3c_rate_limit, "requests per second")?,
+    w3c_cache_ttl:        value(conf, "W3C", "cache_ttl", d.w3c_cache_ttl, "seconds")?,
+    chatter_interval:     value(conf, "AI", "chatter_interval", d.chatter_interval, "seconds")?,
+    activity:             value(conf, "AI", "activity", d.activity, "positive number")?,
+    // older configs had it only as last voice guild
+    guild:                match conf.get_from(Some("Discord"), "guild") {
+                            Some(_) => value(conf, "Discord", "guild", d.guild, "guild id")?,
+                            None    => value(conf, "Music", "last_guild", d.guild, "guild id")?
+                          },
+    twitch_oauth:         string(conf, "Twitch", "oauth", &d.twitch_oauth),
+    twitch_client_id:     string(conf, "Twitch", "client_id", &d.twitch_client_id),
+    twitch_client_secret: string(conf, "Twitch", "client_secret", &d.twitch_client_secret),
+    db_path:              string(conf, "Database", "path", &d.db_path),
+    db_capacity:          value(conf, "Database", "capacity", d.db_capacity, "size in bytes")?,
+    archive_days:         value(conf, "Points", "archive_days", d.archive_days, "number of days")?
   };
-  let config_load_status =
-    Ini::load_from_file(CONF_FILE_NAME)
-      .and_then(|conf| Ok({
-        options.discord               = conf["Discord"]["token"].to_owned();
-        options.rejoin                = &(conf["Music"]["rejoin"]) == "true";
-        options.last_guild            = conf["Music"]["last_guild"].to_owned();
-        options.last_channel          = conf["Music"]["last_channel"].to_owned();
-        options.last_stream           = conf["Music"]["last_stream"].to_owned();
-        options.twitch_oauth          = conf["Twitch"]["oauth"].to_owned();
-        options.twitch_client_id      = conf["Twitch"]["client_id"].to_owned();
-        options.twitch_client_secret  = conf["Twitch"]["client_secret"].to_owned();
-      }));
-  if config_load_status.is_err() {
-    write_config(&options);
-  }
-  options
+  check(!options.prefix.is_empty(), "Discord.prefix", &options.prefix, "non empty prefix")?;
+  check(GATEWAYS.contains(&options.gateway), "W3C.gateway", &options.gateway.to_string(), "10, 20 or 30")?;
+  check(options.live_check_interval > 0, "W3C.live_check_interval", "0", "seconds")?;
+  check(options.w3c_api.starts_with("http"), "W3C.api", &options.w3c_api, "http(s) url")?;
+  check(options.w3c_timeout > 0, "W3C.timeout", "0", "seconds")?;
+  check(options.w3c_rate_limit > 0, "W3C.rate_limit", "0", "requests per second")?;
+  check(options.chatter_interval > 0, "AI.chatter_interval", "0", "seconds")?;
+  check(options.activity > 0, "AI.activity", "0", "positive number")?;
+  check(!options.db_path.is_empty(), "Database.path", "", "file name")?;
+  Ok(options)
+}
+
+fn load(path: &str) -> Result<AOptions, Error> {
+  let mut conf = Ini::load_from_file(path).map_err(|why| Error::Load(path.to_string(), why.to_string()))?;
+  for (var, section, key) in ENV_OVERRIDES {
+    if let Ok(value) = env::var(var) {
+      conf.with_section(Some(section.to_string())).set(*key, value);
+    }
+  }
+  from_ini(&conf)
+}
+
+/// token is only needed to connect so offline commands work without it
+pub fn check_token(options: &AOptions) -> Result<(), Error> {
+  check(!options.discord.is_empty() && options.discord != "0", "Discord.token", &options.discord, "bot token")
+}
+
+/// Loads configuration from `path`, missing file is created with defaults
+pub fn parse_config(path: &str) -> Result<AOptions, Error> {
+  if !Path::new(path).exists() {
+    let defaults = AOptions::default();
+    to_ini(&defaults).write_to_file(path).map_err(|why| Error::Load(path.to_string(), why.to_string()))?;
+    return Err(Error::Created(path.to_string()));
+  }
+  let options = load(path)?;
+  if let Ok(mut conf_path) = CONF_PATH.write() {
+    *conf_path = path.to_string();
+  }
+  if let Ok(mut current) = CURRENT.write() {
+    *current = options.clone();
+  }
+  Ok(options)
+}
+
+/// Keys changed by reload
+#[derive(Debug, Default)]
+pub struct Reloaded {
+  /// already used
+  pub applied: Vec<&'static str>,
+  /// changed in file but old values are used until restart
+  pub restart: Vec<&'static str>
+}
+
+macro_rules! changed {
+  ($old:ident, $new:ident, $out:expr, $($field:ident => $key:expr),*) => {
+    $( if $old.$field != $new.$field { $out.push($key); } )*
+  };
+}
+
+/// Reads config file again, on error current configuration stays
+pub fn reload() -> Result<(AOptions, Reloaded), Error> {
+  let path = CONF_PATH.read().map(|p| p.clone()).unwrap_or(String::from(CONF_FILE_NAME));
+  let old = current();
+  let mut new = load(path.as_str())?;
+  let mut reloaded = Reloaded::default();
+  changed!(old, new, reloaded.applied,
+    log_channel           => "Channels.log",
+    main_channel          => "Channels.main",
+    digest_channel        => "Channels.digest",
+    gateway               => "W3C.gateway",
+    live_check_interval   => "W3C.live_check_interval",
+    w3c_api               => "W3C.api",
+    w3c_timeout           => "W3C.timeout",
+    w3c_retries           => "W3C.retries",
+    w3c_rate_limit        => "W3C.rate_limit",
+    w3c_cache_ttl         => "W3C.cache_ttl",
+    chatter_interval      => "AI.chatter_interval",
+    activity              => "AI.activity",
+    twitch_oauth          => "Twitch.oauth",
+    twitch_client_id      => "Twitch.client_id",
+    twitch_client_secret  => "Twitch.client_secret",
+    archive_days          => "Points.archive_days");
+  changed!(old, new, reloaded.restart,
+    discord               => "Discord.token",
+    prefix                => "Discord.prefix",
+    guild                 => "Discord.guild",
+    db_path               => "Database.path",
+    db_capacity           => "Database.capacity");
+  // running client, framework and database still use these
+  new.discord     = old.discord;
+  new.prefix      = old.prefix;
+  new.guild       = old.guild;
+  new.db_path     = old.db_path;
+  new.db_capacity = old.db_capacity;
+  if let Ok(mut current) = CURRENT.write() {
+    *current = new.clone();
+  }
+  Ok((new, reloaded))
+}
+
+/// voice state from `[Music]` section, it was kept in config before `state`
+pub fn legacy_voice(path: &str) -> Option<state::Voice> {
+  let conf = Ini::load_from_file(path).ok()?;
+  let music = conf.section(Some("Music"))?;
+  let id = |key: &str| music.get(key).and_then(|v| v.trim().parse::<u64>().ok()).unwrap_or(0);
+  Some(state::Voice { rejoin: music.get("rejoin").map(|r| r.trim() == "true").unwrap_or(false)
+                    , guild: id("last_guild")
+                    , channel: id("last_channel")
+                    , stream: music.get("last_stream").unwrap_or("").trim().to_string() })
 }
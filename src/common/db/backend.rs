@@ -0,0 +1,89 @@
+use super::{ Error, keys::Key };
+
+use cannyls::lump::LumpData;
+use cannyls::nvm::FileNvm;
+use cannyls::storage::Storage;
+
+use std::path::Path;
+
+#[cfg(test)]
+use std::collections::BTreeMap;
+
+/// Key-value storage under transactions
+pub trait Backend: Send {
+  fn get(&mut self, key: &Key) -> Result<Option<Vec<u8>>, Error>;
+  fn put(&mut self, key: &Key, data: &[u8]) -> Result<(), Error>;
+  fn delete(&mut self, key: &Key) -> Result<(), Error>;
+  /// keys in `[from, to)` in order
+  fn range(&mut self, from: &Key, to: &Key) -> Result<Vec<Key>, Error>;
+}
+
+pub struct Cannyls {
+  storage: Storage<FileNvm>
+}
+
+impl Cannyls {
+  /// opens storage file or creates it with given capacity in bytes
+  pub fn open(path: &str, capacity: u64) -> Result<Cannyls, Error> {
+    let storage =
+      if !Path::new(path).exists() {
+        let f = FileNvm::create(path, capacity)?;
+        Storage::create(f)?
+      } else {
+        let f = FileNvm::open(path)?;
+        Storage::open(f)?
+      };
+    Ok(Cannyls { storage: storage })
+  }
+}
+
+impl Backend for Cannyls {
+  fn get(&mut self, key: &Key) -> Result<Option<Vec<u8>>, Error> {
+    Ok(self.storage.get(&key.lump_id())?
+               .map(|data| data.as_bytes().to_vec()))
+  }
+  fn put(&mut self, key: &Key, data: &[u8]) -> Result<(), Error> {
+    self.storage.put(&key.lump_id(), &LumpData::new(data.to_vec())?)?;
+    Ok(())
+  }
+  fn delete(&mut self, key: &Key) -> Result<(), Error> {
+    self.storage.delete(&key.lump_id())?;
+    Ok(())
+  }
+  fn range(&mut self, from: &Key, to: &Key) -> Result<Vec<Key>, Error> {
+    Ok(self.storage.list_range(from.lump_id()..to.lump_id())
+                   .into_iter().map(Key::from).collect())
+  }
+}
+
+/// Nothing is saved, for tests
+#[cfg(test)]
+#[derive(Default)]
+pub struct Memory {
+  lumps: BTreeMap<Key, Vec<u8>>
+}
+
+#[cfg(test)]
+impl Memory {
+  pub fn new() -> Memory {
+    Memory::default()
+  }
+}
+
+#[cfg(test)]
+impl Backend for Memory {
+  fn get(&mut self, key: &Key) -> Result<Option<Vec<u8>>, Error> {
+    Ok(self.lumps.get(key).cloned())
+  }
+  fn put(&mut self, key: &Key, data: &[u8]) -> Result<(), Error> {
+    self.lumps.insert(*key, data.to_vec());
+    Ok(())
+  }
+  fn delete(&mut self, key: &Key) -> Result<(), Error> {
+    self.lumps.remove(key);
+    Ok(())
+  }
+  fn range(&mut self, from: &Key, to: &Key) -> Result<Vec<Key>, Error> {
+    Ok(self.lumps.range(*from..*to).map(|(k, _)| *k).collect())
+  }
+}
@@ -0,0 +1,119 @@
+/*
+* Lump ids layout
+* high 64 bits are scope and low 64 bits are id inside of that scope
+* points are keyed (guild, user) since the beginning
+* Discord snowflakes will fit in 62 bits until 2049
+* so two highest bits of scope are free to mark other tables
+*/
+
+use cannyls::lump::LumpId;
+
+const ARCHIVE: u64 = 0b01 << 62;
+const LEDGER: u64 = 0b10 << 62;
+const TABLES: u64 = 0b11 << 62;
+
+/// Tables living in the tagged part of scope, id meaning depends on table
+#[derive(Clone, Copy, Debug)]
+pub enum Table {
+  /// guild id → point tiers
+  Tiers   = 1,
+  /// 0 → writes of transaction being commited
+  Journal = 2,
+  /// guild id → recent transfers and other notable points changes
+  Audit   = 3,
+  /// guild id → anti-farming rules for chat points
+  PointRules = 4,
+  /// guild id → chat points multipliers of channels
+  Multipliers = 5,
+  /// 0 → version of database layout
+  Schema = 6,
+  /// `state::Slot` → runtime state
+  State = 7,
+  /// guild id → channels and behavior of guild
+  Settings = 8,
+  /// 0 → lines chain is trained on besides chat messages
+  Corpus = 9,
+  /// 0 → players tracked in live games
+  Roster = 10,
+  /// user id → battletag linked by member
+  Links = 11,
+  /// 0 → live games being tracked
+  Games = 12,
+  /// day since epoch → finished games of roster players
+  History = 13
+}
+
+/// What lives under the key, decided by scope tag
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+  Points,
+  Archive,
+  Ledger,
+  /// table number, see `Table`
+  Table(u64)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Key {
+  pub scope: u64,
+  pub id: u64
+}
+
+impl Key {
+  pub fn points(guild_id: u64, user_id: u64) -> Key {
+    Key { scope: guild_id, id: user_id }
+  }
+
+  /// points of member who left the guild
+  pub fn archive(guild_id: u64, user_id: u64) -> Key {
+    Key { scope: ARCHIVE | guild_id, id: user_id }
+  }
+
+  /// first archived key of all guilds and first key after them
+  pub fn archive_range() -> (Key, Key) {
+    (Key { scope: ARCHIVE, id: 0 }, Key { scope: LEDGER, id: 0 })
+  }
+
+  /// guild id of points, archive or ledger key
+  pub fn guild(&self) -> u64 {
+    self.scope & !TABLES
+  }
+
+  pub fn ledger(guild_id: u64, user_id: u64) -> Key {
+    Key { scope: LEDGER | guild_id, id: user_id }
+  }
+
+  pub fn table(table: Table, id: u64) -> Key {
+    Key { scope: TABLES | table as u64, id: id }
+  }
+
+  /// first and last key of the scope
+  pub fn scope_range(scope: u64) -> (Key, Key) {
+    (Key { scope: scope, id: 0 }, Key { scope: scope, id: u64::MAX })
+  }
+
+  /// first and last possible keys
+  pub fn all() -> (Key, Key) {
+    (Key { scope: 0, id: 0 }, Key { scope: u64::MAX, id: u64::MAX })
+  }
+
+  pub fn kind(&self) -> Kind {
+    match self.scope & TABLES {
+      0       => Kind::Points,
+      ARCHIVE => Kind::Archive,
+      LEDGER  => Kind::Ledger,
+      _       => Kind::Table(self.scope & !TABLES)
+    }
+  }
+
+  pub fn lump_id(&self) -> LumpId {
+    LumpId::new((self.scope as u128) << 64 | self.id as u128) // >
+  }
+}
+
+impl From<LumpId> for Key {
+  fn from(lump_id: LumpId) -> Key {
+    let u = lump_id.as_u128();
+    Key { scope: (u >> 64) as u64, id: u as u64 }
+  }
+}
@@ -0,0 +1,339 @@
+/*
+* Local database
+* backend is owned by single thread and everyone else sends it transactions
+* so blocking I/O never happens on async executor
+*/
+
+pub mod keys;
+pub mod backend;
+
+use keys::{ Key, Table };
+use backend::Backend;
+
+use serde::{ Serialize, de::DeserializeOwned };
+
+use tokio::sync::oneshot;
+
+use std::{
+  fmt,
+  thread,
+  sync::{ mpsc, Mutex }
+};
+
+#[derive(Debug)]
+pub enum Error {
+  Storage(cannyls::Error),
+  Codec(bincode::Error),
+  Version(u8),
+  Closed
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::Storage(why) => write!(f, "storage error: {}", why),
+      Error::Codec(why)   => write!(f, "failed to encode or decode record: {}", why),
+      Error::Version(v)   => write!(f, "record has unknown version {}", v),
+      Error::Closed       => write!(f, "storage thread is not running")
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl From<cannyls::Error> for Error {
+  fn from(why: cannyls::Error) -> Error { Error::Storage(why) }
+}
+
+impl From<bincode::Error> for Error {
+  fn from(why: bincode::Error) -> Error { Error::Codec(why) }
+}
+
+/*
+* Every record starts with magic and version of its layout,
+* records written before that have no envelope and count as version 0
+*/
+const MAGIC: &[u8; 4] = b"AMDS";
+
+/// Something stored in database
+pub trait Record: Serialize + DeserializeOwned {
+  /// bump when layout changes and decode previous layouts in `upgrade`
+  const VERSION: u8 = 1;
+
+  /// decodes record of older version
+  fn upgrade(version: u8, body: &[u8]) -> Result<Self, Error> {
+    match version {
+      0 => Ok(bincode::deserialize(body)?),
+      v => Err(Error::Version(v))
+    }
+  }
+}
+
+impl Record for u32 {}
+
+/// version and body of stored record
+pub fn version(bytes: &[u8]) -> (u8, &[u8]) {
+  if bytes.len() > MAGIC.len() && bytes.starts_with(MAGIC) {
+    (bytes[MAGIC.len()], &bytes[MAGIC.len() + 1..])
+  } else {
+    (0, bytes)
+  }
+}
+
+pub fn encode<T: Record>(value: &T) -> Result<Vec<u8>, Error> {
+  let mut bytes = MAGIC.to_vec();
+  bytes.push(T::VERSION);
+  bytes.extend(bincode::serialize(value)?);
+  Ok(bytes)
+}
+
+pub fn decode<T: Record>(bytes: &[u8]) -> Result<T, Error> {
+  let (version, body) = version(bytes);
+  if version == T::VERSION {
+    Ok(bincode::deserialize(body)?)
+  } else if version > T::VERSION {
+    Err(Error::Version(version))
+  } else {
+    T::upgrade(version, body)
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum Write {
+  Put(Key, Vec<u8>),
+  Delete(Key)
+}
+
+impl Record for Vec<Write> {}
+
+/// Transaction, reads go to backend (or own writes)
+/// writes are buffered and applied all together on commit
+pub struct Tx<'a> {
+  backend: &'a mut dyn Backend,
+  writes: Vec<Write>
+}
+
+impl<'a> Tx<'a> {
+  pub fn new(backend: &'a mut dyn Backend) -> Tx<'a> {
+    Tx { backend: backend, writes: Vec::new() }
+  }
+
+  pub fn get(&mut self, key: &Key) -> Result<Option<Vec<u8>>, Error> {
+    for write in self.writes.iter().rev() {
+      match write {
+        Write::Put(k, data) if k == key => return Ok(Some(data.clone())),
+        Write::Delete(k) if k == key    => return Ok(None),
+        _ => ()
+      }
+    }
+    self.backend.get(key)
+  }
+
+  /// keys commited in `[from, to)`, own writes are not included
+  pub fn range(&mut self, from: &Key, to: &Key) -> Result<Vec<Key>, Error> {
+    self.backend.range(from, to)
+  }
+
+  pub fn put(&mut self, key: Key, data: Vec<u8>) {
+    self.writes.push(Write::Put(key, data));
+  }
+
+  pub fn delete(&mut self, key: Key) {
+    self.writes.push(Write::Delete(key));
+  }
+
+  pub fn load<T: Record>(&mut self, key: &Key) -> Result<Option<T>, Error> {
+    match self.get(key)? {
+      Some(bytes) => Ok(Some(decode(&bytes)?)),
+      None        => Ok(None)
+    }
+  }
+
+  pub fn store<T: Record>(&mut self, key: Key, value: &T) -> Result<(), Error> {
+    let bytes = encode(value)?;
+    self.put(key, bytes);
+    Ok(())
+  }
+
+  pub fn commit(self) -> Result<(), Error> {
+    let Tx { backend, writes } = self;
+    if writes.len() > 1 {
+      // writes are saved first so if we die in the middle
+      // they will be replayed on next start
+      let journal = Key::table(Table::Journal, 0);
+      let intent = encode(&writes)?;
+      backend.put(&journal, &intent)?;
+      apply(backend, &writes)?;
+      backend.delete(&journal)?;
+    } else {
+      apply(backend, &writes)?;
+    }
+    Ok(())
+  }
+}
+
+fn apply(backend: &mut dyn Backend, writes: &[Write]) -> Result<(), Error> {
+  for write in writes {
+    match write {
+      Write::Put(key, data) => backend.put(key, data)?,
+      Write::Delete(key)    => backend.delete(key)?
+    }
+  }
+  Ok(())
+}
+
+/// finishes transaction interrupted by crash or kill
+fn recover(backend: &mut dyn Backend) -> Result<(), Error> {
+  let journal = Key::table(Table::Journal, 0);
+  if let Some(intent) = backend.get(&journal)? {
+    let writes: Vec<Write> = decode(&intent)?;
+    warn!("replaying {} writes of interrupted transaction", writes.len());
+    apply(backend, &writes)?;
+    backend.delete(&journal)?;
+  }
+  Ok(())
+}
+
+type Job = Box<dyn FnOnce(&mut dyn Backend) + Send>;
+
+/// Handle to storage thread, does nothing until started with some backend
+pub struct Db {
+  jobs: Mutex<Option<mpsc::Sender<Job>>>
+}
+
+impl Db {
+  pub fn new() -> Db {
+    Db { jobs: Mutex::new(None) }
+  }
+
+  /// Starts storage thread owning `backend`, previous one stops
+  pub fn start(&self, mut backend: Box<dyn Backend>) -> Result<(), Error> {
+    recover(backend.as_mut())?;
+    let (sender, receiver) = mpsc::channel::<Job>();
+    thread::spawn(move || {
+      for job in receiver {
+        job(backend.as_mut());
+      }
+    });
+    if let Ok(mut jobs) = self.jobs.lock() {
+      *jobs = Some(sender);
+    }
+    Ok(())
+  }
+
+  /// Runs `f` on storage thread and commits its writes if it returns `Ok`
+  pub async fn transaction<T, E, F>(&self, f: F) -> Result<T, E>
+    where F: FnOnce(&mut Tx<'_>) -> Result<T, E> + Send + 'static
+        , T: Send + 'static
+        , E: From<Error> + Send + 'static {
+    let (reply, result) = oneshot::channel::<Result<T, E>>();
+    let job: Job = Box::new(move |backend: &mut dyn Backend| {
+      let mut tx = Tx::new(backend);
+      let out = match f(&mut tx) {
+        Ok(value) => tx.commit().map(|_| value).map_err(E::from),
+        Err(why)  => Err(why)
+      };
+      let _ = reply.send(out);
+    });
+    let sent = self.jobs.lock()
+                   .map(|jobs| jobs.as_ref().map(|j| j.send(job).is_ok()).unwrap_or(false))
+                   .unwrap_or(false);
+    if !sent {
+      return Err(E::from(Error::Closed));
+    }
+    result.await.unwrap_or_else(|_| Err(E::from(Error::Closed)))
+  }
+}
+
+lazy_static! {
+  pub static ref DB: Db = Db::new();
+}
+
+/// Starts database on cannyls file
+pub fn open(path: &str, capacity: u64) -> Result<(), Error> {
+  DB.start(Box::new(backend::Cannyls::open(path, capacity)?))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use backend::Memory;
+  use crate::common::{
+    points::{ self, Points },
+    ledger::Reason
+  };
+
+  const GUILD: u64 = 1;
+
+  async fn count(db: &Db, user_id: u64) -> u64 {
+    db.transaction(move |tx| points::get(tx, GUILD, user_id)).await.unwrap()
+  }
+
+  #[test]
+  fn decodes_record_without_envelope() {
+    let raw = bincode::serialize(&Points { count: 5, role: 7 }).unwrap();
+    assert_eq!(version(&raw).0, 0);
+    let points: Points = decode(&raw).unwrap();
+    assert_eq!((points.count, points.role), (5, 7));
+    let points: Points = decode(&encode(&points).unwrap()).unwrap();
+    assert_eq!((points.count, points.role), (5, 7));
+  }
+
+  #[test]
+  fn newer_record_is_refused() {
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(Points::VERSION + 1);
+    bytes.extend(bincode::serialize(&Points::default()).unwrap());
+    match decode::<Points>(&bytes) {
+      Err(Error::Version(v)) => assert_eq!(v, Points::VERSION + 1),
+      other => panic!("expected Version error, got {:?}", other)
+    }
+  }
+
+  #[tokio::test]
+  async fn failed_give_changes_nothing() {
+    let db = Db::new();
+    db.start(Box::new(Memory::new())).unwrap();
+    db.transaction(|tx| points::add(tx, GUILD, 2, 3, Reason::Chat)).await.unwrap();
+    db.transaction(|tx| points::add(tx, GUILD, 3, 1, Reason::Chat)).await.unwrap();
+    match db.transaction(|tx| points::give(tx, GUILD, 2, 3, 4)).await {
+      Err(points::Error::NotEnough(3)) => (),
+      other => panic!("expected NotEnough(3), got {:?}", other)
+    }
+    assert_eq!(count(&db, 2).await, 3);
+    assert_eq!(count(&db, 3).await, 1);
+  }
+
+  #[tokio::test]
+  async fn failed_transaction_drops_writes() {
+    let db = Db::new();
+    db.start(Box::new(Memory::new())).unwrap();
+    let failed = db.transaction(|tx| -> Result<(), points::Error> {
+      tx.store(Key::points(GUILD, 2), &Points { count: 5, role: 0 })?;
+      tx.store(Key::points(GUILD, 3), &Points { count: 5, role: 0 })?;
+      Err(points::Error::NoPoints)
+    }).await;
+    assert!(failed.is_err());
+    assert_eq!(count(&db, 2).await, 0);
+    assert_eq!(count(&db, 3).await, 0);
+  }
+
+  #[tokio::test]
+  async fn interrupted_commit_is_replayed() {
+    let mut memory = Memory::new();
+    set!{ kept    = Key::points(GUILD, 2)
+        , gone    = Key::points(GUILD, 3)
+        , journal = Key::table(Table::Journal, 0) };
+    memory.put(&gone, &encode(&Points { count: 7, role: 0 }).unwrap()).unwrap();
+    // commit died after journal was written and before writes were applied
+    let writes = vec![ Write::Put(kept, encode(&Points { count: 5, role: 0 }).unwrap())
+                     , Write::Delete(gone) ];
+    memory.put(&journal, &encode(&writes).unwrap()).unwrap();
+    let db = Db::new();
+    db.start(Box::new(memory)).unwrap();
+    assert_eq!(count(&db, 2).await, 5);
+    assert_eq!(count(&db, 3).await, 0);
+    let left = db.transaction(move |tx| tx.get(&journal)).await.unwrap();
+    assert!(left.is_none());
+  }
+}
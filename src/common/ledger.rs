@@ -0,0 +1,124 @@
+/*
+* Points ledger
+* every member has their recent changes in one record
+* and every guild has log of transfers and other notable changes for admins
+*/
+
+use crate::common::db::{
+  self, DB, Tx, Record,
+  keys::{ Key, Table }
+};
+
+use chrono::{ Utc, TimeZone };
+
+use std::fmt;
+
+static HISTORY_MAX : usize = 50;
+static AUDIT_MAX : usize = 100;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Reason {
+  Chat,
+  Game,
+  Give,
+  Leave,
+  Import,
+  Rejoin,
+  /// changed by admin (id) with given reason
+  Admin(u64, String)
+}
+
+impl fmt::Display for Reason {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Reason::Chat    => write!(f, "chatting"),
+      Reason::Game    => write!(f, "tracked game won"),
+      Reason::Give    => write!(f, "transfer"),
+      Reason::Leave   => write!(f, "left the server"),
+      Reason::Import  => write!(f, "restored from backup"),
+      Reason::Rejoin  => write!(f, "came back"),
+      Reason::Admin(by, why) => write!(f, "<@{}>: {}", by, why)
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Entry {
+  pub timestamp: i64,
+  pub guild: u64,
+  pub user: u64,
+  pub delta: i64,
+  pub reason: Reason,
+  /// other side of transfer, 0 if there is none
+  pub counterparty: u64
+}
+
+impl Record for Vec<Entry> {}
+
+impl Entry {
+  pub fn new(guild: u64, user: u64, delta: i64, reason: Reason, counterparty: u64) -> Entry {
+    Entry { timestamp: Utc::now().timestamp()
+          , guild: guild
+          , user: user
+          , delta: delta
+          , reason: reason
+          , counterparty: counterparty }
+  }
+
+  pub fn date(&self) -> String {
+    Utc.timestamp(self.timestamp, 0).format("%d.%m.%Y %H:%M").to_string()
+  }
+}
+
+fn push(entries: &mut Vec<Entry>, entry: &Entry, max: usize) {
+  // chatting gives a lot of +1 so they are merged by day
+  if entry.reason == Reason::Chat {
+    if let Some(last) = entries.last_mut() {
+      if last.reason == Reason::Chat
+      && Utc.timestamp(last.timestamp, 0).date() == Utc.timestamp(entry.timestamp, 0).date() {
+        last.delta += entry.delta;
+        last.timestamp = entry.timestamp;
+        return;
+      }
+    }
+  }
+  entries.push(entry.clone());
+  if entries.len() > max {
+    let extra = entries.len() - max;
+    entries.drain(..extra);
+  }
+}
+
+/// adds entry to history of entry user
+pub fn record(tx: &mut Tx, entry: &Entry) -> Result<(), db::Error> {
+  let key = Key::ledger(entry.guild, entry.user);
+  let mut entries: Vec<Entry> = tx.load(&key)?.unwrap_or_default();
+  push(&mut entries, entry, HISTORY_MAX);
+  tx.store(key, &entries)
+}
+
+/// adds entry to guild log visible for admins
+pub fn audit(tx: &mut Tx, entry: &Entry) -> Result<(), db::Error> {
+  let key = Key::table(Table::Audit, entry.guild);
+  let mut entries: Vec<Entry> = tx.load(&key)?.unwrap_or_default();
+  push(&mut entries, entry, AUDIT_MAX);
+  tx.store(key, &entries)
+}
+
+/// recent entries of member, newest first
+pub async fn history(guild_id: u64, user_id: u64) -> Result<Vec<Entry>, db::Error> {
+  DB.transaction(move |tx| -> Result<Vec<Entry>, db::Error> {
+    let mut entries: Vec<Entry> = tx.load(&Key::ledger(guild_id, user_id))?.unwrap_or_default();
+    entries.reverse();
+    Ok(entries)
+  }).await
+}
+
+/// recent notable entries of guild, newest first
+pub async fn audit_log(guild_id: u64) -> Result<Vec<Entry>, db::Error> {
+  DB.transaction(move |tx| -> Result<Vec<Entry>, db::Error> {
+    let mut entries: Vec<Entry> = tx.load(&Key::table(Table::Audit, guild_id))?.unwrap_or_default();
+    entries.reverse();
+    Ok(entries)
+  }).await
+}
@@ -0,0 +1,182 @@
+/*
+* Battletag links
+* members link own battletag with ~link, it's used by ~stats right away
+* but player joins roster only after admin approves it
+*/
+
+use crate::common::{
+  db::{ self, DB, Tx, Record, keys::{ Key, Table } },
+  roster::{ self, Division, Member },
+  types::Player
+};
+
+use std::borrow::Cow;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Link {
+  pub battletag: String,
+  pub approved: bool,
+  /// w3champions gateway, 0 for configured one
+  pub gateway: u32
+}
+
+impl Record for Link {
+  const VERSION: u8 = 2;
+  fn upgrade(version: u8, body: &[u8]) -> Result<Self, db::Error> {
+    match version {
+      // links were made only on default gateway
+      1 => {
+        let (battletag, approved): (String, bool) = bincode::deserialize(body)?;
+        Ok(Link { battletag: battletag, approved: approved, gateway: 0 })
+      },
+      v => Err(db::Error::Version(v))
+    }
+  }
+}
+
+pub async fn get(user_id: u64) -> Result<Option<Link>, db::Error> {
+  DB.transaction(move |tx| tx.load(&Key::table(Table::Links, user_id))).await
+}
+
+/// drops roster player added by approved `link` of `user_id`
+fn leave_roster(tx: &mut Tx, user_id: u64, link: &Link) -> Result<(), db::Error> {
+  if link.approved {
+    let _ = roster::edit(tx, |members| -> Result<(), ()> {
+      members.retain(|m| !(m.is(&link.battletag) && m.player.discord == user_id));
+      Ok(())
+    })?;
+  }
+  Ok(())
+}
+
+fn store_link(tx: &mut Tx, user_id: u64, link: &Link) -> Result<(), db::Error> {
+  let key = Key::table(Table::Links, user_id);
+  if let Some(old) = tx.load::<Link>(&key)? {
+    leave_roster(tx, user_id, &old)?;
+  }
+  tx.store(key, link)
+}
+
+fn remove_link(tx: &mut Tx, user_id: u64) -> Result<Option<Link>, db::Error> {
+  let key = Key::table(Table::Links, user_id);
+  let link: Option<Link> = tx.load(&key)?;
+  if let Some(link) = &link {
+    leave_roster(tx, user_id, link)?;
+    tx.delete(key);
+  }
+  Ok(link)
+}
+
+fn approve_link(tx: &mut Tx, user_id: u64) -> Result<Option<Link>, db::Error> {
+  let key = Key::table(Table::Links, user_id);
+  match tx.load::<Link>(&key)? {
+    Some(mut link) => {
+      link.approved = true;
+      tx.store(key, &link)?;
+      let _ = roster::edit(tx, |members| -> Result<(), ()> {
+        if !members.iter().any(|m| m.is(&link.battletag)) {
+          members.push(Member { division: Division::Interesting
+                              , player: Player { battletag: Cow::Owned(link.battletag.clone())
+                                               , discord: user_id
+                                               , streams: None
+                                               , gateway: link.gateway } });
+        }
+        Ok(())
+      })?;
+      Ok(Some(link))
+    },
+    None => Ok(None)
+  }
+}
+
+/// links battletag to member, new link always waits for approval
+/// player of previously approved link leaves roster
+pub async fn link(user_id: u64, battletag: String, gateway: u32) -> Result<(), db::Error> {
+  let link = Link { battletag: battletag, approved: false, gateway: gateway };
+  DB.transaction(move |tx| store_link(tx, user_id, &link)).await
+}
+
+/// removes link and player added by it from roster
+pub async fn unlink(user_id: u64) -> Result<Option<Link>, db::Error> {
+  DB.transaction(move |tx| remove_link(tx, user_id)).await
+}
+
+/// links waiting for approval
+pub async fn pending() -> Result<Vec<(u64, Link)>, db::Error> {
+  DB.transaction(|tx| -> Result<Vec<(u64, Link)>, db::Error> {
+    let (from, to) = Key::scope_range(Key::table(Table::Links, 0).scope);
+    let mut out = Vec::new();
+    for key in tx.range(&from, &to)? {
+      match tx.load::<Link>(&key) {
+        Ok(Some(link)) => if !link.approved { out.push((key.id, link)) },
+        Ok(None) => (),
+        Err(why) => warn!("skipping link {:?}: {}", key, why)
+      }
+    }
+    Ok(out)
+  }).await
+}
+
+/// approves link and adds player to roster, returns approved battletag
+pub async fn approve(user_id: u64) -> Result<Option<String>, db::Error> {
+  let approved = DB.transaction(move |tx| approve_link(tx, user_id)).await?;
+  Ok(approved.map(|l| l.battletag))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::common::db::backend::Memory;
+
+  fn run<T>(memory: &mut Memory, f: impl FnOnce(&mut Tx) -> Result<T, db::Error>) -> T {
+    let mut tx = Tx::new(memory);
+    let out = f(&mut tx).unwrap();
+    tx.commit().unwrap();
+    out
+  }
+
+  fn roster(memory: &mut Memory) -> Vec<Member> {
+    run(memory, |tx| tx.load(&Key::table(Table::Roster, 0))).unwrap_or_default()
+  }
+
+  fn new_link(battletag: &str) -> Link {
+    Link { battletag: battletag.to_string(), approved: false, gateway: 0 }
+  }
+
+  #[test]
+  fn upgrades_link_without_gateway() {
+    let mut bytes = b"AMDS".to_vec();
+    bytes.push(1);
+    bytes.extend(bincode::serialize(&(String::from("Foo#1234"), true)).unwrap());
+    let link: Link = db::decode(&bytes).unwrap();
+    assert_eq!(link.battletag, "Foo#1234");
+    assert!(link.approved);
+    assert_eq!(link.gateway, 0);
+  }
+
+  #[test]
+  fn relink_removes_approved_player() {
+    let mut memory = Memory::new();
+    run(&mut memory, |tx| store_link(tx, 5, &new_link("Foo#1234")));
+    run(&mut memory, |tx| approve_link(tx, 5));
+    assert_eq!(roster(&mut memory).len(), 1);
+    run(&mut memory, |tx| store_link(tx, 5, &new_link("Bar#4321")));
+    assert!(roster(&mut memory).is_empty());
+    let link: Link = run(&mut memory, |tx| tx.load(&Key::table(Table::Links, 5))).unwrap();
+    assert_eq!(link.battletag, "Bar#4321");
+    assert!(!link.approved);
+  }
+
+  #[test]
+  fn unlink_keeps_players_of_others() {
+    let mut memory = Memory::new();
+    run(&mut memory, |tx| store_link(tx, 5, &new_link("Foo#1234")));
+    run(&mut memory, |tx| approve_link(tx, 5));
+    run(&mut memory, |tx| store_link(tx, 6, &new_link("Bar#4321")));
+    run(&mut memory, |tx| approve_link(tx, 6));
+    assert!(run(&mut memory, |tx| remove_link(tx, 5)).is_some());
+    let members = roster(&mut memory);
+    assert_eq!(members.len(), 1);
+    assert!(members[0].is("bar#4321"));
+  }
+}
@@ -0,0 +1,130 @@
+/*
+* Database migrations
+* runs on start before anything else touches database
+* and rewrites records stored in older layouts with current one
+*/
+
+use crate::common::{
+  db::{ self, DB, Db, Tx, Record, keys::{ Key, Kind, Table } },
+  points::{ Points, Archived, Tier },
+  ledger::Entry,
+  antifarm::Rules,
+  state::{ Slot, Voice },
+  settings::Settings,
+  roster::Member,
+  links::Link
+};
+
+use crate::stains::{
+  ai::chain::Corpus,
+  cyber::{ types::TrackingGame, history::Played }
+};
+
+use std::collections::{ BTreeMap, HashMap };
+
+/// bump together with `Record::VERSION` of anything
+static SCHEMA : u32 = 5;
+
+/// rewrites record if it's older than current layout
+/// records that fail to decode are left as is
+fn upgrade<T: Record>(tx: &mut Tx, key: Key) -> Result<bool, db::Error> {
+  if let Some(bytes) = tx.get(&key)? {
+    if db::version(&bytes).0 < T::VERSION {
+      match db::decode::<T>(&bytes) {
+        Ok(value) => {
+          tx.store(key, &value)?;
+          return Ok(true);
+        },
+        Err(why) => warn!("skipping record {:?}: {}", key, why)
+      }
+    }
+  }
+  Ok(false)
+}
+
+fn upgrade_key(tx: &mut Tx, key: Key) -> Result<bool, db::Error> {
+  match key.kind() {
+    Kind::Points  => upgrade::<Points>(tx, key),
+    Kind::Archive => upgrade::<Archived>(tx, key),
+    Kind::Ledger  => upgrade::<Vec<Entry>>(tx, key),
+    Kind::Table(t) if t == Table::Tiers as u64       => upgrade::<Vec<Tier>>(tx, key),
+    Kind::Table(t) if t == Table::Audit as u64       => upgrade::<Vec<Entry>>(tx, key),
+    Kind::Table(t) if t == Table::PointRules as u64  => upgrade::<Rules>(tx, key),
+    Kind::Table(t) if t == Table::Multipliers as u64 => upgrade::<BTreeMap<u64, u64>>(tx, key),
+    Kind::Table(t) if t == Table::Settings as u64    => upgrade::<Settings>(tx, key),
+    Kind::Table(t) if t == Table::Corpus as u64      => upgrade::<Corpus>(tx, key),
+    Kind::Table(t) if t == Table::Roster as u64      => upgrade::<Vec<Member>>(tx, key),
+    Kind::Table(t) if t == Table::Links as u64       => upgrade::<Link>(tx, key),
+    Kind::Table(t) if t == Table::Games as u64       => upgrade::<HashMap<String, TrackingGame>>(tx, key),
+    Kind::Table(t) if t == Table::History as u64     => upgrade::<Vec<Played>>(tx, key),
+    Kind::Table(t) if t == Table::State as u64 =>
+      if key.id == Slot::Voice as u64 {
+        upgrade::<Voice>(tx, key)
+      } else {
+        upgrade::<u32>(tx, key)
+      },
+    // journal is replayed before migrations and schema is written after them
+    Kind::Table(t) if t == Table::Journal as u64
+                   || t == Table::Schema as u64      => Ok(false),
+    Kind::Table(t) => {
+      warn!("unknown table {} in database, skipping {:?}", t, key);
+      Ok(false)
+    }
+  }
+}
+
+pub async fn run() -> Result<(), db::Error> {
+  migrate(&DB).await
+}
+
+async fn migrate(db: &Db) -> Result<(), db::Error> {
+  let schema_key = Key::table(Table::Schema, 0);
+  let schema = db.transaction(move |tx| -> Result<u32, db::Error> {
+    Ok(tx.load(&schema_key)?.unwrap_or(0))
+  }).await?;
+  if schema >= SCHEMA {
+    return Ok(());
+  }
+  info!("migrating database from schema {} to {}", schema, SCHEMA);
+  let keys = db.transaction(|tx| -> Result<Vec<Key>, db::Error> {
+    let (from, to) = Key::all();
+    tx.range(&from, &to)
+  }).await?;
+  let mut upgraded = 0;
+  // one transaction per record so huge database doesn't go to journal at once
+  for key in keys {
+    if db.transaction(move |tx| upgrade_key(tx, key)).await? {
+      upgraded += 1;
+    }
+  }
+  db.transaction(move |tx| tx.store(schema_key, &SCHEMA)).await?;
+  info!("{} records migrated", upgraded);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::common::db::backend::{ Backend, Memory };
+
+  #[tokio::test]
+  async fn broken_record_is_skipped() {
+    let mut memory = Memory::new();
+    set!{ old    = Key::points(1, 2)
+        , broken = Key::points(1, 3) };
+    memory.put(&old, &bincode::serialize(&Points { count: 5, role: 0 }).unwrap()).unwrap();
+    memory.put(&broken, &[1, 2, 3]).unwrap();
+    let db = Db::new();
+    db.start(Box::new(memory)).unwrap();
+    migrate(&db).await.unwrap();
+    let (old_bytes, broken_bytes, schema) = db.transaction(move |tx|
+      -> Result<(Option<Vec<u8>>, Option<Vec<u8>>, Option<u32>), db::Error> {
+      Ok((tx.get(&old)?, tx.get(&broken)?, tx.load(&Key::table(Table::Schema, 0))?))
+    }).await.unwrap();
+    let old_bytes = old_bytes.unwrap();
+    assert_eq!(db::version(&old_bytes).0, Points::VERSION);
+    assert_eq!(db::decode::<Points>(&old_bytes).unwrap().count, 5);
+    assert_eq!(broken_bytes, Some(vec![1, 2, 3]));
+    assert_eq!(schema, Some(SCHEMA));
+  }
+}
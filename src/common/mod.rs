@@ -1,7 +1,18 @@
+#[macro_use] pub mod macros;
 pub mod types;
 pub mod msg;
 pub mod log;
 pub mod conf;
+pub mod state;
+pub mod settings;
+pub mod roster;
+pub mod links;
+pub mod db;
+pub mod migrate;
 pub mod points;
+pub mod ledger;
+pub mod tiers;
+pub mod antifarm;
+pub mod multipliers;
+pub mod backup;
 pub mod help;
-#[macro_use] pub mod macros;
@@ -0,0 +1,70 @@
+/*
+* Chat points multipliers per channel
+* channels without multiplier give 1 point, 0 means no points at all
+*/
+
+use crate::common::db::{
+  self, DB, Record,
+  keys::{ Key, Table }
+};
+
+use std::{
+  collections::{ BTreeMap, HashMap },
+  sync::RwLock
+};
+
+/// channel id → multiplier
+impl Record for BTreeMap<u64, u64> {}
+
+lazy_static! {
+  // read on every message so kept in memory after first load
+  static ref CACHE: RwLock<HashMap<u64, BTreeMap<u64, u64>>> = RwLock::new(HashMap::new());
+}
+
+pub async fn get_multipliers(guild_id: u64) -> Result<BTreeMap<u64, u64>, db::Error> {
+  if let Some(multipliers) = CACHE.read().ok().and_then(|c| c.get(&guild_id).cloned()) {
+    return Ok(multipliers);
+  }
+  let multipliers = DB.transaction(move |tx| -> Result<BTreeMap<u64, u64>, db::Error> {
+    Ok(tx.load(&Key::table(Table::Multipliers, guild_id))?.unwrap_or_default())
+  }).await?;
+  if let Ok(mut cache) = CACHE.write() {
+    cache.insert(guild_id, multipliers.clone());
+  }
+  Ok(multipliers)
+}
+
+pub async fn get_multiplier(guild_id: u64, channel_id: u64) -> Result<u64, db::Error> {
+  let multipliers = get_multipliers(guild_id).await?;
+  Ok(multipliers.get(&channel_id).cloned().unwrap_or(1))
+}
+
+/// setting 1 removes multiplier of channel
+pub async fn set_multiplier(guild_id: u64, channel_id: u64, multiplier: u64) -> Result<(), db::Error> {
+  let multipliers = DB.transaction(move |tx| -> Result<BTreeMap<u64, u64>, db::Error> {
+    let key = Key::table(Table::Multipliers, guild_id);
+    let mut multipliers: BTreeMap<u64, u64> = tx.load(&key)?.unwrap_or_default();
+    if multiplier == 1 {
+      multipliers.remove(&channel_id);
+    } else {
+      multipliers.insert(channel_id, multiplier);
+    }
+    if multipliers.is_empty() {
+      tx.delete(key);
+    } else {
+      tx.store(key, &multipliers)?;
+    }
+    Ok(multipliers)
+  }).await?;
+  if let Ok(mut cache) = CACHE.write() {
+    cache.insert(guild_id, multipliers);
+  }
+  Ok(())
+}
+
+/// multipliers are read from database again on next use
+pub fn forget_cached() {
+  if let Ok(mut cache) = CACHE.write() {
+    cache.clear();
+  }
+}
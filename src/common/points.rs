@@ -1,155 +1,560 @@
-use cannyls::lump::{LumpData, LumpId};
-use cannyls::nvm::FileNvm;
-use cannyls::storage::Storage;
+use crate::common::{
+  db::{ self, DB, Tx, Record, keys::{ Key, Table } },
+  ledger::{ self, Entry, Reason }
+};
 
-use std::path::Path;
+use chrono::Utc;
 
-use serde::{Deserialize, Serialize};
+use std::{ collections::HashSet, fmt };
 
-use tokio::task;
-use tokio::sync::{ Mutex };
+/// records restored in one transaction
+const RESTORE_CHUNK : usize = 200;
 
-#[derive(Serialize, Deserialize)]
-struct Points {
-  count: u64,
-  role: u64
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Points {
+  pub count: u64,
+  pub role: u64
 }
 
-fn get_storage() -> Storage<FileNvm> {
-  let db_name = "tree.lusf";
-  if !Path::new(db_name).exists() {
-    let f = FileNvm::create(db_name, 666_666_666).unwrap();
-    let storage: Storage<FileNvm> = Storage::create(f).unwrap();
-    storage
+impl Record for Points {}
+
+/// Role given to everyone with at least `points` points
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Tier {
+  pub points: u64,
+  pub role: u64
+}
+
+impl Record for Vec<Tier> {}
+
+/// Points of member who left, kept for a while in case they come back
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Archived {
+  pub points: Points,
+  /// unix time of leaving
+  pub left: i64
+}
+
+impl Record for Archived {}
+
+/// Manual correction of points by admin
+#[derive(Clone, Copy, Debug)]
+pub enum Adjustment {
+  Grant(u64),
+  Revoke(u64),
+  Set(u64),
+  Reset
+}
+
+impl Adjustment {
+  fn apply(&self, count: u64) -> u64 {
+    match self {
+      Adjustment::Grant(n)  => count + n,
+      Adjustment::Revoke(n) => count.saturating_sub(*n),
+      Adjustment::Set(n)    => *n,
+      Adjustment::Reset     => 0
+    }
+  }
+}
+
+impl fmt::Display for Adjustment {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Adjustment::Grant(n)  => write!(f, "granted {} points", n),
+      Adjustment::Revoke(n) => write!(f, "revoked {} points", n),
+      Adjustment::Set(n)    => write!(f, "set points to {}", n),
+      Adjustment::Reset     => write!(f, "reset points")
+    }
+  }
+}
+
+/// Points of member before and after adjustment
+#[derive(Clone, Debug)]
+pub struct Adjusted {
+  pub user: u64,
+  pub old: u64,
+  pub new: u64
+}
+
+/// Member has crossed a tier threshold, role ids are 0 for no tier
+#[derive(Clone, Debug)]
+pub struct TierChange {
+  pub user: u64,
+  pub old: u64,
+  pub new: u64
+}
+
+#[derive(Debug)]
+pub enum Error {
+  NoPoints,
+  NotEnough(u64),
+  Db(db::Error)
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::NoPoints     => write!(f, "you have no points to give"),
+      Error::NotEnough(_) => write!(f, "not enough points to give"),
+      Error::Db(why)      => write!(f, "error accessing points: {}", why)
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl From<db::Error> for Error {
+  fn from(why: db::Error) -> Error { Error::Db(why) }
+}
+
+fn load_tiers(tx: &mut Tx, guild_id: u64) -> Result<Vec<Tier>, db::Error> {
+  Ok(tx.load(&Key::table(Table::Tiers, guild_id))?.unwrap_or_default())
+}
+
+/// role of the highest tier reached with `count` points or 0
+fn tier_role(tiers: &[Tier], count: u64) -> u64 {
+  tiers.iter()
+       .filter(|t| t.points <= count)
+       .max_by_key(|t| t.points)
+       .map(|t| t.role)
+       .unwrap_or(0)
+}
+
+/// updates tier of points and returns change if there is one
+fn update_tier(tiers: &[Tier], user_id: u64, points: &mut Points) -> Option<TierChange> {
+  let role = tier_role(tiers, points.count);
+  if role != points.role {
+    let change = TierChange { user: user_id, old: points.role, new: role };
+    points.role = role;
+    Some(change)
   } else {
-    let f = FileNvm::open(db_name).unwrap();
-    let storage: Storage<FileNvm> = Storage::open(f).unwrap();
-    storage
+    None
   }
 }
 
-lazy_static! {
-  pub static ref STORAGE: Mutex<Storage<FileNvm>> = Mutex::new(get_storage());
+/// `(user_id, points)` of every record in the guild
+fn guild_points(tx: &mut Tx, guild_id: u64) -> Result<Vec<(u64, Points)>, db::Error> {
+  let (from, to) = Key::scope_range(guild_id);
+  let mut all = Vec::new();
+  for key in tx.range(&from, &to)? {
+    match tx.load::<Points>(&key) {
+      Ok(Some(points)) => all.push((key.id, points)),
+      Ok(None) => (),
+      // one broken record shouldn't break whole guild
+      Err(why) => warn!("skipping points record {:?}: {}", key, why)
+    }
+  }
+  Ok(all)
 }
 
-pub async fn add_points( guild_id: u64
-                       , user_id: u64
-                       , new_points: u64) {
-  let mut storage = STORAGE.lock().await;
-  let u64_2: u128 = (guild_id as u128) << 64 | user_id as u128; // >
-  let lump_id: LumpId = LumpId::new(u64_2);
-  task::spawn_blocking(move || {
-    match storage.get(&lump_id) {
-      Ok(mbdata) => {
-        if let Some(mut data) = mbdata {
-          let byte_data: &mut [u8] = data.as_bytes_mut();
-          let mut points : Points = bincode::deserialize(byte_data).unwrap();
-          points.count += new_points;
-          let new_bytes = bincode::serialize(&points).unwrap();
-          (*byte_data).copy_from_slice(&new_bytes[..]);
-          let added: bool = storage.put(&lump_id, &data).unwrap();
-          if added {
-            error!("error updating points");
-          }
-        } else {
-          let points = Points { count: 0, role: 0 };
-          let encoded: Vec<u8> = bincode::serialize(&points).unwrap();
-          let lump_data: LumpData = LumpData::new(encoded).unwrap();
-          let added: bool = storage.put(&lump_id, &lump_data).unwrap();
-          if !added {
-            error!("error on points initialization");
-          }
-        }
-      }, Err(why) => {
-        error!("Failed to get key: {:?}", why);
+/*
+* Points logic works on any transaction so it doesn't care about backend
+* async functions below just run it on database
+*/
+
+pub fn add( tx: &mut Tx
+          , guild_id: u64
+          , user_id: u64
+          , new_points: u64
+          , reason: Reason ) -> Result<Option<TierChange>, Error> {
+  let tiers = load_tiers(tx, guild_id)?;
+  let key = Key::points(guild_id, user_id);
+  let mut points: Points = tx.load(&key)?.unwrap_or_default();
+  points.count += new_points;
+  let change = update_tier(&tiers, user_id, &mut points);
+  tx.store(key, &points)?;
+  ledger::record(tx, &Entry::new(guild_id, user_id, new_points as i64, reason, 0))?;
+  Ok(change)
+}
+
+pub fn give( tx: &mut Tx
+           , guild_id: u64
+           , user_id: u64
+           , target_user_id: u64
+           , points_count: u64 ) -> Result<Vec<TierChange>, Error> {
+  let tiers = load_tiers(tx, guild_id)?;
+  set!{ key         = Key::points(guild_id, user_id)
+      , target_key  = Key::points(guild_id, target_user_id) };
+  let mut points: Points = tx.load(&key)?.ok_or(Error::NoPoints)?;
+  if points.count < points_count {
+    return Err(Error::NotEnough(points.count));
+  }
+  let mut target_points: Points = tx.load(&target_key)?.unwrap_or_default();
+  points.count -= points_count;
+  target_points.count += points_count;
+  let mut changes = Vec::new();
+  changes.extend(update_tier(&tiers, user_id, &mut points));
+  changes.extend(update_tier(&tiers, target_user_id, &mut target_points));
+  tx.store(key, &points)?;
+  tx.store(target_key, &target_points)?;
+  let given = Entry::new(guild_id, user_id, -(points_count as i64), Reason::Give, target_user_id);
+  ledger::record(tx, &given)?;
+  ledger::audit(tx, &given)?;
+  ledger::record(tx, &Entry::new(guild_id, target_user_id, points_count as i64, Reason::Give, user_id))?;
+  Ok(changes)
+}
+
+pub fn get(tx: &mut Tx, guild_id: u64, user_id: u64) -> Result<u64, Error> {
+  let points: Option<Points> = tx.load(&Key::points(guild_id, user_id))?;
+  Ok(points.map(|p| p.count).unwrap_or(0))
+}
+
+pub fn archive(tx: &mut Tx, guild_id: u64, user_id: u64) -> Result<bool, Error> {
+  let key = Key::points(guild_id, user_id);
+  if let Some(points) = tx.load::<Points>(&key)? {
+    let left = Entry::new(guild_id, user_id, -(points.count as i64), Reason::Leave, 0);
+    ledger::record(tx, &left)?;
+    ledger::audit(tx, &left)?;
+    tx.store(Key::archive(guild_id, user_id), &Archived { points: points, left: left.timestamp })?;
+    tx.delete(key);
+    Ok(true)
+  } else {
+    Ok(false)
+  }
+}
+
+/// Brings archived points back, tier is recalculated since tiers could change meanwhile
+/// returned change has no old role because member comes back without roles
+pub fn unarchive(tx: &mut Tx, guild_id: u64, user_id: u64) -> Result<Option<TierChange>, Error> {
+  let archive_key = Key::archive(guild_id, user_id);
+  if let Some(archived) = tx.load::<Archived>(&archive_key)? {
+    let tiers = load_tiers(tx, guild_id)?;
+    let key = Key::points(guild_id, user_id);
+    // in case member got points between leaving and rejoin event
+    let mut points: Points = tx.load(&key)?.unwrap_or_default();
+    points.count += archived.points.count;
+    points.role = tier_role(&tiers, points.count);
+    tx.store(key, &points)?;
+    tx.delete(archive_key);
+    let back = Entry::new(guild_id, user_id, archived.points.count as i64, Reason::Rejoin, 0);
+    ledger::record(tx, &back)?;
+    ledger::audit(tx, &back)?;
+    Ok(Some(TierChange { user: user_id, old: 0, new: points.role }))
+  } else {
+    Ok(None)
+  }
+}
+
+/// Forgets archived members who left before `before` (unix time) with their history
+/// returns how many were purged
+pub fn purge(tx: &mut Tx, before: i64) -> Result<usize, Error> {
+  let (from, to) = Key::archive_range();
+  let mut purged = 0;
+  for key in tx.range(&from, &to)? {
+    let archived = match tx.load::<Archived>(&key) {
+      Ok(archived) => archived,
+      Err(why) => {
+        warn!("skipping archived record {:?}: {}", key, why);
+        None
+      }
+    };
+    if let Some(archived) = archived {
+      if archived.left < before {
+        tx.delete(Key::ledger(key.guild(), key.id));
+        tx.delete(key);
+        purged += 1;
       }
     }
-  }).await.unwrap();
+  }
+  Ok(purged)
+}
+
+/// Applies adjustment to every user, reset removes records completely
+pub fn adjust( tx: &mut Tx
+             , guild_id: u64
+             , users: &[u64]
+             , adjustment: Adjustment
+             , admin_id: u64
+             , reason: &str ) -> Result<(Vec<Adjusted>, Vec<TierChange>), Error> {
+  let tiers = load_tiers(tx, guild_id)?;
+  setm!{ adjusted  = Vec::new()
+       , changes   = Vec::new() };
+  for &user_id in users {
+    let key = Key::points(guild_id, user_id);
+    let mut points: Points = tx.load(&key)?.unwrap_or_default();
+    let old = points.count;
+    points.count = adjustment.apply(old);
+    changes.extend(update_tier(&tiers, user_id, &mut points));
+    if let Adjustment::Reset = adjustment {
+      tx.delete(key);
+    } else {
+      tx.store(key, &points)?;
+    }
+    let entry = Entry::new( guild_id, user_id, points.count as i64 - old as i64
+                          , Reason::Admin(admin_id, reason.to_string()), 0 );
+    ledger::record(tx, &entry)?;
+    ledger::audit(tx, &entry)?;
+    adjusted.push(Adjusted { user: user_id, old: old, new: points.count });
+  }
+  Ok((adjusted, changes))
+}
+
+pub fn top(tx: &mut Tx, guild_id: u64) -> Result<Vec<(u64, u64)>, Error> {
+  let mut top: Vec<(u64, u64)> =
+    guild_points(tx, guild_id)?.into_iter()
+                               .map(|(user_id, p)| (user_id, p.count))
+                               .collect();
+  top.sort_by(|(_, a), (_, b)| b.cmp(a));
+  Ok(top)
+}
+
+pub fn tiers(tx: &mut Tx, guild_id: u64) -> Result<Vec<Tier>, Error> {
+  let mut tiers = load_tiers(tx, guild_id)?;
+  tiers.sort_by_key(|t| t.points);
+  Ok(tiers)
 }
 
+pub fn update_tiers(tx: &mut Tx, guild_id: u64, tiers: &[Tier]) -> Result<(), Error> {
+  let key = Key::table(Table::Tiers, guild_id);
+  if tiers.is_empty() {
+    tx.delete(key);
+  } else {
+    tx.store(key, &tiers.to_vec())?;
+  }
+  Ok(())
+}
+
+pub fn retier(tx: &mut Tx, guild_id: u64) -> Result<Vec<(u64, u64)>, Error> {
+  let tiers = load_tiers(tx, guild_id)?;
+  let mut roles: Vec<(u64, u64)> = Vec::new();
+  for (user_id, mut points) in guild_points(tx, guild_id)? {
+    if update_tier(&tiers, user_id, &mut points).is_some() {
+      tx.store(Key::points(guild_id, user_id), &points)?;
+    }
+    roles.push((user_id, points.role));
+  }
+  Ok(roles)
+}
+
+/// Writes imported counts, tier is taken from current tiers and not from backup
+/// since tiers and their roles could change after backup was made
+pub fn restore_records( tx: &mut Tx
+                      , guild_id: u64
+                      , records: &[(u64, Points)] ) -> Result<usize, Error> {
+  let tiers = load_tiers(tx, guild_id)?;
+  let mut changed = 0;
+  for (user_id, imported) in records {
+    let key = Key::points(guild_id, *user_id);
+    let old: Points = tx.load(&key)?.unwrap_or_default();
+    let points = Points { count: imported.count, role: tier_role(&tiers, imported.count) };
+    if old.count != points.count || old.role != points.role {
+      let delta = points.count as i64 - old.count as i64;
+      tx.store(key, &points)?;
+      ledger::record(tx, &Entry::new(guild_id, *user_id, delta, Reason::Import, 0))?;
+      changed += 1;
+    }
+  }
+  Ok(changed)
+}
+
+/// Removes points of members missing in backup, `(user_id, count)`
+pub fn forget_records(tx: &mut Tx, guild_id: u64, users: &[(u64, u64)]) -> Result<usize, Error> {
+  for (user_id, count) in users {
+    tx.delete(Key::points(guild_id, *user_id));
+    ledger::record(tx, &Entry::new(guild_id, *user_id, -(*count as i64), Reason::Import, 0))?;
+  }
+  Ok(users.len())
+}
+
+pub async fn add_points( guild_id: u64
+                       , user_id: u64
+                       , new_points: u64
+                       , reason: Reason ) -> Result<Option<TierChange>, Error> {
+  DB.transaction(move |tx| add(tx, guild_id, user_id, new_points, reason)).await
+}
+
+/// Moves points from one member to another, both records are commited together
 pub async fn give_points( guild_id: u64
                         , user_id: u64
                         , target_user_id: u64
-                        , points_count: u64) -> (bool, String) {
-  let mut storage = STORAGE.lock().await;
-  let u64_2: u128 = (guild_id as u128) << 64 | user_id as u128; // >
-  let tu64_2: u128 = (guild_id as u128) << 64 | target_user_id as u128; // >
-  let lump_id: LumpId = LumpId::new(u64_2);
-  let target_lump_id: LumpId = LumpId::new(tu64_2);
-  match storage.get(&lump_id) {
-    Ok(mbdata) => {
-      if let Some(mut data) = mbdata {
-        let byte_data: &mut [u8] = data.as_bytes_mut();
-        let mut points : Points = bincode::deserialize(byte_data).unwrap();
-        if points.count >= points_count {
-          points.count -= points_count;
-          let new_bytes = bincode::serialize(&points).unwrap();
-          (*byte_data).copy_from_slice(&new_bytes[..]);
-          let added: bool = storage.put(&lump_id, &data).unwrap();
-          if added {
-            error!("Some strange error updating giver points");
-          }
-          match storage.get(&target_lump_id) {
-            Ok(tmbdata) => {
-              if let Some(mut tdata) = tmbdata {
-                let tbyte_data: &mut [u8] = tdata.as_bytes_mut();
-                let mut tpoints : Points = bincode::deserialize(tbyte_data).unwrap();
-                tpoints.count += points_count;
-                let tnew_bytes = bincode::serialize(&tpoints).unwrap();
-                (*tbyte_data).copy_from_slice(&tnew_bytes[..]);
-                let tadded: bool = storage.put(&target_lump_id, &tdata).unwrap();
-                if tadded {
-                  error!("Some strange error updating receiver points");
-                }
-              } else {
-                let tpoints = Points { count: points_count, role: 0 };
-                let tencoded: Vec<u8> = bincode::serialize(&tpoints).unwrap();
-                let tlump_data: LumpData = LumpData::new(tencoded).unwrap();
-                let tadded: bool = storage.put(&target_lump_id, &tlump_data).unwrap();
-                if !tadded {
-                  error!("Some strange error updating receiver points");
-                }
-              }
-              (true, format!("{} points transfered", points_count))
-            }, Err(why) => {
-              error!("Failed to get key: {:?}", why);
-              (false, String::from("error accessing points"))
-            }
-          }
-        } else {
-          (false, String::from("not enough points to give"))
-        }
-      } else {
-        (false, String::from("you have no points to give"))
-      }
-    }, Err(why) => {
-      error!("Failed to get key: {:?}", why);
-      (false, String::from("error accessing points"))
+                        , points_count: u64 ) -> Result<Vec<TierChange>, Error> {
+  DB.transaction(move |tx| give(tx, guild_id, user_id, target_user_id, points_count)).await
+}
+
+pub async fn get_points(guild_id: u64, user_id: u64) -> Result<u64, Error> {
+  DB.transaction(move |tx| get(tx, guild_id, user_id)).await
+}
+
+/// Moves points of leaving member to archive
+/// returns `true` if there was something to archive
+pub async fn archive_points(guild_id: u64, user_id: u64) -> Result<bool, Error> {
+  DB.transaction(move |tx| archive(tx, guild_id, user_id)).await
+}
+
+pub async fn restore_archived(guild_id: u64, user_id: u64) -> Result<Option<TierChange>, Error> {
+  DB.transaction(move |tx| unarchive(tx, guild_id, user_id)).await
+}
+
+/// Purges members archived longer than `days` ago
+pub async fn purge_archive(days: u64) -> Result<usize, Error> {
+  let before = Utc::now().timestamp() - (days * 24 * 60 * 60) as i64;
+  DB.transaction(move |tx| purge(tx, before)).await
+}
+
+/// Admin correction of points of several members in one transaction
+pub async fn adjust_points( guild_id: u64
+                          , users: Vec<u64>
+                          , adjustment: Adjustment
+                          , admin_id: u64
+                          , reason: String ) -> Result<(Vec<Adjusted>, Vec<TierChange>), Error> {
+  DB.transaction(move |tx| adjust(tx, guild_id, &users, adjustment, admin_id, &reason)).await
+}
+
+/// Every points record of the guild as `(user_id, points)`, highest score first
+pub async fn get_top(guild_id: u64) -> Result<Vec<(u64, u64)>, Error> {
+  DB.transaction(move |tx| top(tx, guild_id)).await
+}
+
+pub async fn get_tiers(guild_id: u64) -> Result<Vec<Tier>, Error> {
+  DB.transaction(move |tx| tiers(tx, guild_id)).await
+}
+
+pub async fn set_tiers(guild_id: u64, tiers: Vec<Tier>) -> Result<(), Error> {
+  DB.transaction(move |tx| update_tiers(tx, guild_id, &tiers)).await
+}
+
+/// Recalculates tier of every points record in the guild
+/// returns `(user_id, role)` for all of them so roles could be fixed on Discord side
+pub async fn resync_tiers(guild_id: u64) -> Result<Vec<(u64, u64)>, Error> {
+  DB.transaction(move |tx| retier(tx, guild_id)).await
+}
+
+/// Every points record of the guild as is, for backups
+pub async fn dump(guild_id: u64) -> Result<Vec<(u64, Points)>, Error> {
+  DB.transaction(move |tx| -> Result<Vec<(u64, Points)>, Error> {
+    Ok(guild_points(tx, guild_id)?)
+  }).await
+}
+
+/// Writes records by `RESTORE_CHUNK` in transaction so huge guild doesn't go to journal at once,
+/// with `overwrite` members missing in records lose their points
+/// returns how many records were changed, roles on Discord side need `tiers::resync` after it
+pub async fn restore( guild_id: u64
+                    , records: Vec<(u64, Points)>
+                    , overwrite: bool ) -> Result<usize, Error> {
+  let mut changed = 0;
+  if overwrite {
+    let imported: HashSet<u64> = records.iter().map(|(user_id, _)| *user_id).collect();
+    let missing = DB.transaction(move |tx| -> Result<Vec<(u64, u64)>, Error> {
+      Ok(guild_points(tx, guild_id)?.into_iter()
+           .filter(|(user_id, _)| !imported.contains(user_id))
+           .map(|(user_id, points)| (user_id, points.count))
+           .collect())
+    }).await?;
+    for chunk in missing.chunks(RESTORE_CHUNK) {
+      let chunk = chunk.to_vec();
+      changed += DB.transaction(move |tx| forget_records(tx, guild_id, &chunk)).await?;
     }
   }
+  for chunk in records.chunks(RESTORE_CHUNK) {
+    let chunk = chunk.to_vec();
+    changed += DB.transaction(move |tx| restore_records(tx, guild_id, &chunk)).await?;
+  }
+  Ok(changed)
 }
 
-pub async fn get_points(guild_id: u64, user_id: u64) -> Result<u64, cannyls::Error> {
-  let mut storage = STORAGE.lock().await;
-  task::spawn_blocking(move || {
-    let u64_2: u128 = (guild_id as u128) << 64 | user_id as u128; // >
-    let lump_id: LumpId = LumpId::new(u64_2);
-    if let Ok(mbdata) = storage.get(&lump_id) {
-      if let Some(mut data) = mbdata {
-        let byte_data: &mut [u8] = data.as_bytes_mut();
-        let points : Points = bincode::deserialize(byte_data).unwrap();
-        return Ok(points.count);
-      }
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::common::db::backend::Memory;
+
+  const GUILD: u64 = 1;
+
+  /// runs `f` in its own transaction and commits it, ranges only see commited keys
+  fn run<T>(memory: &mut Memory, f: impl FnOnce(&mut Tx) -> Result<T, Error>) -> Result<T, Error> {
+    let mut tx = Tx::new(memory);
+    let out = f(&mut tx)?;
+    tx.commit()?;
+    Ok(out)
+  }
+
+  fn count(memory: &mut Memory, user_id: u64) -> u64 {
+    run(memory, |tx| get(tx, GUILD, user_id)).unwrap()
+  }
+
+  #[test]
+  fn add_accumulates_and_reaches_tier() {
+    let mut memory = Memory::new();
+    run(&mut memory, |tx| update_tiers(tx, GUILD, &[Tier { points: 10, role: 100 }])).unwrap();
+    assert!(run(&mut memory, |tx| add(tx, GUILD, 2, 4, Reason::Chat)).unwrap().is_none());
+    let change = run(&mut memory, |tx| add(tx, GUILD, 2, 6, Reason::Game)).unwrap();
+    assert_eq!(count(&mut memory, 2), 10);
+    assert_eq!(change.map(|c| (c.old, c.new)), Some((0, 100)));
+  }
+
+  #[test]
+  fn give_moves_points() {
+    let mut memory = Memory::new();
+    run(&mut memory, |tx| add(tx, GUILD, 2, 10, Reason::Chat)).unwrap();
+    run(&mut memory, |tx| give(tx, GUILD, 2, 3, 4)).unwrap();
+    assert_eq!(count(&mut memory, 2), 6);
+    assert_eq!(count(&mut memory, 3), 4);
+  }
+
+  #[test]
+  fn give_without_enough_points() {
+    let mut memory = Memory::new();
+    run(&mut memory, |tx| add(tx, GUILD, 2, 3, Reason::Chat)).unwrap();
+    match run(&mut memory, |tx| give(tx, GUILD, 2, 3, 4)) {
+      Err(Error::NotEnough(3)) => (),
+      other => panic!("expected NotEnough(3), got {:?}", other)
     }
-    Ok(0)
-  }).await.unwrap()
-}
+    match run(&mut memory, |tx| give(tx, GUILD, 4, 3, 1)) {
+      Err(Error::NoPoints) => (),
+      other => panic!("expected NoPoints, got {:?}", other)
+    }
+    assert_eq!(count(&mut memory, 2), 3);
+    assert_eq!(count(&mut memory, 3), 0);
+  }
 
-pub async fn clear_points(guild_id: u64, user_id: u64) -> Result<bool, cannyls::Error> {
-  let mut storage = STORAGE.lock().await;
-  task::spawn_blocking(move || {
-    let u64_2: u128 = (guild_id as u128) << 64 | user_id as u128; // >
-    let lump_id: LumpId = LumpId::new(u64_2);
-    storage.delete(&lump_id)
-  }).await.unwrap()
+  #[test]
+  fn archive_and_unarchive() {
+    let mut memory = Memory::new();
+    run(&mut memory, |tx| add(tx, GUILD, 2, 7, Reason::Chat)).unwrap();
+    assert!(run(&mut memory, |tx| archive(tx, GUILD, 2)).unwrap());
+    assert!(!run(&mut memory, |tx| archive(tx, GUILD, 2)).unwrap());
+    assert_eq!(count(&mut memory, 2), 0);
+    // points got meanwhile are kept together with archived ones
+    run(&mut memory, |tx| add(tx, GUILD, 2, 1, Reason::Chat)).unwrap();
+    assert!(run(&mut memory, |tx| unarchive(tx, GUILD, 2)).unwrap().is_some());
+    assert_eq!(count(&mut memory, 2), 8);
+    assert!(run(&mut memory, |tx| unarchive(tx, GUILD, 2)).unwrap().is_none());
+  }
+
+  #[test]
+  fn purge_forgets_only_old_archive() {
+    let mut memory = Memory::new();
+    run(&mut memory, |tx| add(tx, GUILD, 2, 7, Reason::Chat)).unwrap();
+    run(&mut memory, |tx| archive(tx, GUILD, 2)).unwrap();
+    assert_eq!(run(&mut memory, |tx| purge(tx, 0)).unwrap(), 0);
+    let later = Utc::now().timestamp() + 60;
+    assert_eq!(run(&mut memory, |tx| purge(tx, later)).unwrap(), 1);
+    assert!(run(&mut memory, |tx| unarchive(tx, GUILD, 2)).unwrap().is_none());
+    assert_eq!(count(&mut memory, 2), 0);
+  }
+
+  #[test]
+  fn adjust_every_kind() {
+    let mut memory = Memory::new();
+    run(&mut memory, |tx| add(tx, GUILD, 2, 5, Reason::Chat)).unwrap();
+    let adjusting = |memory: &mut Memory, adjustment| {
+      run(memory, |tx| adjust(tx, GUILD, &[2, 3], adjustment, 9, "test")).unwrap().0
+        .into_iter().map(|a| (a.user, a.old, a.new)).collect::<Vec<(u64, u64, u64)>>()
+    };
+    assert_eq!(adjusting(&mut memory, Adjustment::Grant(3)), vec![(2, 5, 8), (3, 0, 3)]);
+    assert_eq!(adjusting(&mut memory, Adjustment::Revoke(4)), vec![(2, 8, 4), (3, 3, 0)]);
+    assert_eq!(adjusting(&mut memory, Adjustment::Set(6)), vec![(2, 4, 6), (3, 0, 6)]);
+    assert_eq!(adjusting(&mut memory, Adjustment::Reset), vec![(2, 6, 0), (3, 6, 0)]);
+    assert!(run(&mut memory, |tx| top(tx, GUILD)).unwrap().is_empty());
+  }
+
+  #[test]
+  fn top_is_ordered_and_per_guild() {
+    let mut memory = Memory::new();
+    for (user_id, n) in &[(2, 5), (3, 9), (4, 1)] {
+      run(&mut memory, |tx| add(tx, GUILD, *user_id, *n, Reason::Chat)).unwrap();
+    }
+    run(&mut memory, |tx| add(tx, GUILD + 1, 5, 100, Reason::Chat)).unwrap();
+    assert_eq!(run(&mut memory, |tx| top(tx, GUILD)).unwrap(), vec![(3, 9), (2, 5), (4, 1)]);
+  }
 }
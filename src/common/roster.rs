@@ -0,0 +1,177 @@
+/*
+* Team roster
+* players tracked by live games checker, changed with ~roster
+* lists from `collections::team` only fill empty database on first start
+*/
+
+use crate::{
+  common::{
+    db::{ self, DB, Tx, Record, keys::{ Key, Table } },
+    types::{ Player, Streams }
+  },
+  collections::team::{ DIVISION1, DIVISION2, INTERESTING }
+};
+
+use std::{ borrow::Cow, fmt, str::FromStr };
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Division {
+  First,
+  Second,
+  Interesting
+}
+
+impl FromStr for Division {
+  type Err = ();
+  fn from_str(s: &str) -> Result<Division, ()> {
+    match s.to_lowercase().as_str() {
+      "1" | "first"       => Ok(Division::First),
+      "2" | "second"      => Ok(Division::Second),
+      "3" | "interesting" => Ok(Division::Interesting),
+      _ => Err(())
+    }
+  }
+}
+
+impl fmt::Display for Division {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Division::First       => write!(f, "Division 1"),
+      Division::Second      => write!(f, "Division 2"),
+      Division::Interesting => write!(f, "Interesting")
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Member {
+  pub division: Division,
+  pub player: Player
+}
+
+/// layout before players had own gateways
+#[derive(Deserialize)]
+struct MemberV1 {
+  division: Division,
+  battletag: Cow<'static, str>,
+  discord: u64,
+  streams: Option<Streams>
+}
+
+impl Record for Vec<Member> {
+  const VERSION: u8 = 2;
+  fn upgrade(version: u8, body: &[u8]) -> Result<Self, db::Error> {
+    match version {
+      1 => {
+        let old: Vec<MemberV1> = bincode::deserialize(body)?;
+        Ok(old.into_iter().map(|m|
+          Member { division: m.division
+                 , player: Player { battletag: m.battletag
+                                  , discord: m.discord
+                                  , streams: m.streams
+                                  , gateway: 0 } }
+        ).collect())
+      },
+      v => Err(db::Error::Version(v))
+    }
+  }
+}
+
+impl Member {
+  /// battletags are matched case insensitive in commands
+  pub fn is(&self, battletag: &str) -> bool {
+    self.player.is(battletag)
+  }
+}
+
+fn compiled_in() -> Vec<Member> {
+  let members = |division, players: &[Player]| players.iter().map(move |p|
+    Member { division: division, player: p.clone() }
+  ).collect::<Vec<Member>>();
+  [ members(Division::First, DIVISION1)
+  , members(Division::Second, DIVISION2)
+  , members(Division::Interesting, INTERESTING) ].concat()
+}
+
+/// fills roster with compiled-in players if it was never stored
+pub async fn seed() -> Result<(), db::Error> {
+  let seeded = DB.transaction(|tx| -> Result<bool, db::Error> {
+    let key = Key::table(Table::Roster, 0);
+    if tx.get(&key)?.is_some() {
+      return Ok(false);
+    }
+    tx.store(key, &compiled_in())?;
+    Ok(true)
+  }).await?;
+  if seeded {
+    info!("roster filled with compiled-in players");
+  }
+  Ok(())
+}
+
+pub async fn members() -> Result<Vec<Member>, db::Error> {
+  DB.transaction(|tx| -> Result<Vec<Member>, db::Error> {
+    Ok(tx.load(&Key::table(Table::Roster, 0))?.unwrap_or_default())
+  }).await
+}
+
+/// all tracked players, empty if roster can't be read
+pub async fn players() -> Vec<Player> {
+  match members().await {
+    Ok(members) => members.into_iter().map(|m| m.player).collect(),
+    Err(why) => {
+      error!("Failed to load roster {:?}", why);
+      Vec::new()
+    }
+  }
+}
+
+/// changes roster in `tx`, it's stored only if `f` returns `Ok`
+pub fn edit<T, E, F>(tx: &mut Tx, f: F) -> Result<Result<T, E>, db::Error>
+  where F: FnOnce(&mut Vec<Member>) -> Result<T, E> {
+  let key = Key::table(Table::Roster, 0);
+  let mut roster: Vec<Member> = tx.load(&key)?.unwrap_or_default();
+  let result = f(&mut roster);
+  if result.is_ok() {
+    tx.store(key, &roster)?;
+  }
+  Ok(result)
+}
+
+/// changes roster, it's stored only if `f` returns `Ok`
+pub async fn update<T, E, F>(f: F) -> Result<Result<T, E>, db::Error>
+  where T: Send + 'static, E: Send + 'static,
+        F: FnOnce(&mut Vec<Member>) -> Result<T, E> + Send + 'static {
+  DB.transaction(move |tx| edit(tx, f)).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn upgrades_members_without_gateway() {
+    // same bincode layout as `MemberV1`
+    let old = vec![(Division::Second, String::from("Foo#1234"), 5u64, None::<Streams>)];
+    let mut bytes = b"AMDS".to_vec();
+    bytes.push(1);
+    bytes.extend(bincode::serialize(&old).unwrap());
+    let members: Vec<Member> = db::decode(&bytes).unwrap();
+    assert_eq!(members.len(), 1);
+    assert_eq!(members[0].division, Division::Second);
+    assert_eq!(members[0].player.battletag, "Foo#1234");
+    assert_eq!(members[0].player.discord, 5);
+    assert_eq!(members[0].player.gateway, 0);
+  }
+
+  #[test]
+  fn matches_battletag_ignoring_case() {
+    let member = Member { division: Division::First
+                        , player: Player { battletag: Cow::Borrowed("foo#1234")
+                                         , discord: 5
+                                         , streams: None
+                                         , gateway: 0 } };
+    assert!(member.is("Foo#1234"));
+    assert!(!member.is("Foo#4321"));
+  }
+}
@@ -0,0 +1,184 @@
+/*
+* Per guild settings
+* channels are bound by id, whatever is not bound falls back to
+* compiled-in channel names and names from config
+*/
+
+use crate::{
+  common::{
+    conf,
+    db::{ self, DB, Record, keys::{ Key, Table } },
+    help::channel::channel_by_name
+  },
+  collections::channels::{ AI_ALLOWED, AI_LEARN }
+};
+
+use serenity::{
+  prelude::*,
+  model::id::{ ChannelId, GuildId }
+};
+
+use std::{
+  collections::HashMap,
+  sync::RwLock
+};
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Settings {
+  /// channels to learn from, `AI_LEARN` names if empty
+  pub ai_learn: Vec<u64>,
+  /// channels to chat in, `AI_ALLOWED` names if empty
+  pub ai_chat: Vec<u64>,
+  /// log channel, 0 to find it by name
+  pub log: u64,
+  /// channel for random chatter, 0 to find it by name
+  pub main: u64,
+  /// activity level of guild, global one if not set
+  pub activity: Option<u32>,
+  /// channel for digest of tracked games, 0 to find it by name
+  pub digest: u64
+}
+
+/// layout before digest channel could be bound
+#[derive(Deserialize)]
+struct SettingsV1 {
+  ai_learn: Vec<u64>,
+  ai_chat: Vec<u64>,
+  log: u64,
+  main: u64,
+  activity: Option<u32>
+}
+
+impl Record for Settings {
+  const VERSION: u8 = 2;
+  fn upgrade(version: u8, body: &[u8]) -> Result<Self, db::Error> {
+    match version {
+      0 | 1 => {
+        let old: SettingsV1 = bincode::deserialize(body)?;
+        Ok(Settings { ai_learn: old.ai_learn
+                    , ai_chat: old.ai_chat
+                    , log: old.log
+                    , main: old.main
+                    , activity: old.activity
+                    , digest: 0 })
+      },
+      v => Err(db::Error::Version(v))
+    }
+  }
+}
+
+impl Settings {
+  pub fn is_learn_channel(&self, channel_id: u64, channel_name: &str) -> bool {
+    if self.ai_learn.is_empty() {
+      AI_LEARN.iter().any(|&c| c == channel_name)
+    } else {
+      self.ai_learn.contains(&channel_id)
+    }
+  }
+
+  pub fn is_chat_channel(&self, channel_id: u64, channel_name: &str) -> bool {
+    if self.ai_chat.is_empty() {
+      AI_ALLOWED.iter().any(|&c| c == channel_name)
+    } else {
+      self.ai_chat.contains(&channel_id)
+    }
+  }
+}
+
+lazy_static! {
+  // read on every message so kept in memory after first load
+  static ref CACHE: RwLock<HashMap<u64, Settings>> = RwLock::new(HashMap::new());
+}
+
+pub async fn get(guild_id: u64) -> Settings {
+  if let Some(settings) = CACHE.read().ok().and_then(|c| c.get(&guild_id).cloned()) {
+    return settings;
+  }
+  let loaded = DB.transaction(move |tx| -> Result<Option<Settings>, db::Error> {
+    tx.load(&Key::table(Table::Settings, guild_id))
+  }).await;
+  let settings = match loaded {
+    Ok(settings) => settings.unwrap_or_default(),
+    Err(why) => {
+      error!("Failed to load settings of {} {:?}", guild_id, why);
+      return Settings::default();
+    }
+  };
+  if let Ok(mut cache) = CACHE.write() {
+    cache.insert(guild_id, settings.clone());
+  }
+  settings
+}
+
+/// changes settings of guild and returns new ones
+pub async fn update<F: FnOnce(&mut Settings) + Send + 'static>(guild_id: u64, f: F) -> Result<Settings, db::Error> {
+  let settings = DB.transaction(move |tx| -> Result<Settings, db::Error> {
+    let key = Key::table(Table::Settings, guild_id);
+    let mut settings: Settings = tx.load(&key)?.unwrap_or_default();
+    f(&mut settings);
+    tx.store(key, &settings)?;
+    Ok(settings)
+  }).await?;
+  if let Ok(mut cache) = CACHE.write() {
+    cache.insert(guild_id, settings.clone());
+  }
+  Ok(settings)
+}
+
+/// settings are read from database again on next use
+pub fn forget_cached() {
+  if let Ok(mut cache) = CACHE.write() {
+    cache.clear();
+  }
+}
+
+async fn bound_or_named(ctx: &Context, guild_id: &GuildId, bound: u64, name: &str) -> Option<ChannelId> {
+  if bound != 0 {
+    Some(ChannelId(bound))
+  } else if let Ok(channels) = guild_id.channels(ctx).await {
+    channel_by_name(ctx, &channels, name).await.map(|(c, _)| *c)
+  } else {
+    None
+  }
+}
+
+pub async fn log_channel(ctx: &Context, guild_id: &GuildId) -> Option<ChannelId> {
+  let settings = get(*guild_id.as_u64()).await;
+  bound_or_named(ctx, guild_id, settings.log, conf::current().log_channel.as_str()).await
+}
+
+/// channel for digest of tracked games, none if it's not bound or named in config
+pub async fn digest_channel(ctx: &Context, guild_id: &GuildId) -> Option<ChannelId> {
+  let settings = get(*guild_id.as_u64()).await;
+  let name = conf::current().digest_channel;
+  if settings.digest == 0 && name.is_empty() {
+    None
+  } else {
+    bound_or_named(ctx, guild_id, settings.digest, name.as_str()).await
+  }
+}
+
+pub async fn main_channel(ctx: &Context, guild_id: &GuildId) -> Option<ChannelId> {
+  let settings = get(*guild_id.as_u64()).await;
+  bound_or_named(ctx, guild_id, settings.main, conf::current().main_channel.as_str()).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn upgrades_settings_without_digest() {
+    // same bincode layout as `SettingsV1`
+    let old = (vec![1u64], Vec::<u64>::new(), 2u64, 3u64, Some(40u32));
+    let mut bytes = b"AMDS".to_vec();
+    bytes.push(1);
+    bytes.extend(bincode::serialize(&old).unwrap());
+    let settings: Settings = db::decode(&bytes).unwrap();
+    assert_eq!(settings.ai_learn, vec![1]);
+    assert_eq!(settings.log, 2);
+    assert_eq!(settings.main, 3);
+    assert_eq!(settings.activity, Some(40));
+    assert_eq!(settings.digest, 0);
+  }
+}
@@ -0,0 +1,72 @@
+/*
+* Runtime state
+* things changed by commands and remembered between restarts
+* live in database so conf.ini is never rewritten
+*/
+
+use crate::common::db::{
+  self, DB, Record,
+  keys::{ Key, Table }
+};
+
+/// ids inside of state table
+#[derive(Clone, Copy, Debug)]
+pub enum Slot {
+  Voice     = 1,
+  Activity  = 2,
+  Season    = 3,
+  /// day digest was last posted
+  Digest    = 4
+}
+
+/// Voice channel to rejoin after restart and what was playing there
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct Voice {
+  pub rejoin: bool,
+  pub guild: u64,
+  pub channel: u64,
+  pub stream: String
+}
+
+impl Record for Voice {}
+
+pub async fn load<T: Record + Send + 'static>(slot: Slot) -> Result<Option<T>, db::Error> {
+  DB.transaction(move |tx| tx.load(&Key::table(Table::State, slot as u64))).await
+}
+
+pub async fn store<T: Record + Send + 'static>(slot: Slot, value: T) -> Result<(), db::Error> {
+  DB.transaction(move |tx| tx.store(Key::table(Table::State, slot as u64), &value)).await
+}
+
+pub async fn voice() -> Voice {
+  match load::<Voice>(Slot::Voice).await {
+    Ok(voice) => voice.unwrap_or_default(),
+    Err(why) => {
+      error!("Failed to load voice state {:?}", why);
+      Voice::default()
+    }
+  }
+}
+
+/// changes voice state only if `f` changes something
+pub async fn update_voice<F: FnOnce(&mut Voice)>(f: F) {
+  let mut voice = voice().await;
+  let old = voice.clone();
+  f(&mut voice);
+  if old != voice {
+    if let Err(why) = store(Slot::Voice, voice).await {
+      error!("Failed to save voice state {:?}", why);
+    }
+  }
+}
+
+/// one time move of voice state from `[Music]` section of old configs
+pub async fn migrate_voice(legacy: Option<Voice>) -> Result<(), db::Error> {
+  if let Some(voice) = legacy {
+    if load::<Voice>(Slot::Voice).await?.is_none() {
+      store(Slot::Voice, voice).await?;
+      info!("voice state moved from [Music] section of config, it could be removed now");
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,65 @@
+use crate::common::points::{ self, TierChange };
+
+use serenity::{
+  prelude::*,
+  model::id::{ GuildId, RoleId }
+};
+
+/// Moves member from old tier role to the new one
+pub async fn apply(ctx: &Context, guild_id: &GuildId, change: &TierChange) {
+  if change.old == change.new {
+    return;
+  }
+  if let Ok(mut member) = guild_id.member(ctx, change.user).await {
+    let old_role = RoleId(change.old);
+    let new_role = RoleId(change.new);
+    if change.old != 0 && member.roles.contains(&old_role) {
+      if let Err(why) = member.remove_role(ctx, old_role).await {
+        error!("Failed to remove tier role {:?}", why);
+      }
+    }
+    if change.new != 0 && !member.roles.contains(&new_role) {
+      if let Err(why) = member.add_role(ctx, new_role).await {
+        error!("Failed to add tier role {:?}", why);
+      }
+    }
+  }
+}
+
+pub async fn apply_all(ctx: &Context, guild_id: &GuildId, changes: &[TierChange]) {
+  for change in changes {
+    apply(ctx, guild_id, change).await;
+  }
+}
+
+/// Makes every member with points have exactly the role of their tier
+/// returns how many members were updated
+pub async fn resync(ctx: &Context, guild_id: &GuildId) -> Result<usize, points::Error> {
+  set!{ tiers = points::get_tiers(*guild_id.as_u64()).await?
+      , roles = points::resync_tiers(*guild_id.as_u64()).await? };
+  let tier_roles: Vec<RoleId> = tiers.into_iter().map(|t| RoleId(t.role)).collect();
+  let mut updated = 0;
+  for (user_id, role) in roles {
+    if let Ok(mut member) = guild_id.member(ctx, user_id).await {
+      let mut touched = false;
+      for tier_role in &tier_roles {
+        if tier_role.0 != role && member.roles.contains(tier_role) {
+          if let Err(why) = member.remove_role(ctx, *tier_role).await {
+            error!("Failed to remove tier role {:?}", why);
+          }
+          touched = true;
+        }
+      }
+      if role != 0 && !member.roles.contains(&RoleId(role)) {
+        if let Err(why) = member.add_role(ctx, RoleId(role)).await {
+          error!("Failed to add tier role {:?}", why);
+        }
+        touched = true;
+      }
+      if touched {
+        updated += 1;
+      }
+    }
+  }
+  Ok(updated)
+}
@@ -1,62 +1,106 @@
+use crate::common::conf;
+
+use std::borrow::Cow;
+
 #[derive(Debug, Clone)]
 pub struct AOptions {
-  pub rejoin: bool,
   pub discord: String,
-  pub last_guild: String,
-  pub last_channel: String,
-  pub last_stream: String,
+  pub prefix: String,
+  pub log_channel: String,
+  pub main_channel: String,
+  /// channel for daily and weekly digest of tracked games, empty to not post it
+  pub digest_channel: String,
+  /// w3champions gateway, 20 is Europe
+  pub gateway: u32,
+  /// seconds between live games checks
+  pub live_check_interval: u64,
+  /// w3champions statistic service, could be pointed to local mock
+  pub w3c_api: String,
+  /// seconds before request to w3champions is given up
+  pub w3c_timeout: u64,
+  /// retries of failed request to w3champions
+  pub w3c_retries: u32,
+  /// requests to w3champions per second at most
+  pub w3c_rate_limit: u32,
+  /// seconds responses of w3champions are reused
+  pub w3c_cache_ttl: u64,
+  /// seconds between random messages in main channel
+  pub chatter_interval: u64,
+  /// default activity level, changed one is remembered in `state`
+  pub activity: u32,
+  /// guild for background things like live games and chatter
+  pub guild: u64,
   pub twitch_oauth: String,
   pub twitch_client_id: String,
-  pub twitch_client_secret: String
+  pub twitch_client_secret: String,
+  pub db_path: String,
+  pub db_capacity: u64,
+  pub archive_days: u64
 }
 
-#[derive(Debug, Clone)]
+/// Stream handles, borrowed for compiled-in players and owned for added with `~roster`
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Streams {
-  pub ggru: Option<&'static str>,
-  pub twitch: Option<&'static str>
+  pub ggru: Option<Cow<'static, str>>,
+  pub twitch: Option<Cow<'static, str>>
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Player {
-  pub battletag: &'static str,
+  pub battletag: Cow<'static, str>,
   pub discord: u64,
-  pub streams: Option<Streams>
+  pub streams: Option<Streams>,
+  /// w3champions gateway, 0 for configured one
+  pub gateway: u32
+}
+
+impl Player {
+  pub fn gateway(&self) -> u32 {
+    if self.gateway == 0 { conf::current().gateway } else { self.gateway }
+  }
+
+  /// battletags are typed by hand so case could differ from w3champions one
+  pub fn is(&self, battletag: &str) -> bool {
+    self.battletag.to_lowercase() == battletag.to_lowercase()
+  }
 }
 
 pub const fn player(battletag: &'static str, discord: u64) -> Player {
   Player {
-    battletag: battletag,
+    battletag: Cow::Borrowed(battletag),
     discord: discord,
-    streams: None
+    streams: None,
+    gateway: 0
   }
 }
 
 pub const fn streamer(battletag: &'static str, discord: u64, streams: Option<Streams>) -> Player {
   Player {
-    battletag: battletag,
+    battletag: Cow::Borrowed(battletag),
     discord: discord,
-    streams: streams
+    streams: streams,
+    gateway: 0
   }
 }
 
 pub const fn streams(ggru: &'static str, twitch: &'static str) -> Option<Streams> {
   Some(Streams {
-    ggru: Some(ggru),
-    twitch: Some(twitch)
+    ggru: Some(Cow::Borrowed(ggru)),
+    twitch: Some(Cow::Borrowed(twitch))
   })
 }
 
 pub const fn twitch(twitch: &'static str) -> Option<Streams> {
   Some(Streams {
     ggru: None,
-    twitch: Some(twitch)
+    twitch: Some(Cow::Borrowed(twitch))
   })
 }
 
 #[allow(dead_code)]
 pub const fn ggru(ggru: &'static str) -> Option<Streams> {
   Some(Streams {
-    ggru: Some(ggru),
+    ggru: Some(Cow::Borrowed(ggru)),
     twitch: None
   })
 }
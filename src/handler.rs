@@ -1,16 +1,15 @@
 use crate::{
   stains::gate,
   common::{
-    points,
-    help::{ lang, channel::channel_by_name },
+    points, tiers, ledger, antifarm, multipliers, settings,
+    help::lang,
     types::AOptions,
     msg::{ channel_message }
   },
   stains::ai::chain,
   collections::{
     base::REACTIONS,
-    overwatch::{ OVERWATCH, OVERWATCH_REPLIES },
-    channels::AI_ALLOWED
+    overwatch::{ OVERWATCH, OVERWATCH_REPLIES }
   },
   commands::voice
 };
@@ -59,7 +58,7 @@ impl Handler {
 impl EventHandler for Handler {
   async fn ready(&self, ctx: Context, ready: Ready) {
     info!("Connected as {}", ready.user.name);
-    voice::rejoin_voice_channel(&ctx, &self.options).await;
+    voice::rejoin_voice_channel(&ctx).await;
 
     let threads_check = THREADS.load(Ordering::Relaxed);
     if !threads_check {
@@ -71,39 +70,42 @@ impl EventHandler for Handler {
     info!("Resumed");
   }
   async fn guild_member_addition(&self, ctx: Context, guild_id: GuildId, member: Member) {
-    if let Ok(channels) = guild_id.channels(&ctx).await {
+    match points::restore_archived(guild_id.as_u64().clone(), member.user.id.as_u64().clone()).await {
+      Ok(Some(change)) => tiers::apply(&ctx, &guild_id, &change).await,
+      Ok(None) => (),
+      Err(why) => error!("Failed to restore archived points {:?}", why)
+    }
+    if let Some(channel) = settings::log_channel(&ctx, &guild_id).await {
       let ai_text = chain::generate_with_language(&ctx, &guild_id, false).await;
-      if let Some((channel, _)) = channel_by_name(&ctx, &channels, "log").await {
-        let user = &member.user; // .read().await;
-        let title = format!("has joined here, {}", ai_text.as_str());
-        if let Err(why) = channel.send_message(&ctx, |m| m
-          .embed(|e| {
-            let mut e = e
-              .author(|a| a.icon_url(&user.face()).name(&user.name))
-              .title(title);
-            if let Some(ref joined_at) = member.joined_at {
-              e = e.timestamp(joined_at);
-            } e
-        })).await {
-          error!("Failed to log new user {:?}", why);
-        }
+      let user = &member.user; // .read().await;
+      let title = format!("has joined here, {}", ai_text.as_str());
+      if let Err(why) = channel.send_message(&ctx, |m| m
+        .embed(|e| {
+          let mut e = e
+            .author(|a| a.icon_url(&user.face()).name(&user.name))
+            .title(title);
+          if let Some(ref joined_at) = member.joined_at {
+            e = e.timestamp(joined_at);
+          } e
+      })).await {
+        error!("Failed to log new user {:?}", why);
       }
     }
   }
   async fn guild_member_removal(&self, ctx: Context, guild_id: GuildId, user: User, _: Option<Member>) {
-    let _was_on_chat = points::clear_points(guild_id.as_u64().clone(), user.id.as_u64().clone()).await;
-    if let Ok(channels) = guild_id.channels(&ctx).await {
+    if let Err(why) = points::archive_points(guild_id.as_u64().clone(), user.id.as_u64().clone()).await {
+      error!("Failed to archive points {:?}", why);
+    }
+    if let Some(channel) = settings::log_channel(&ctx, &guild_id).await {
       let ai_text = chain::generate_with_language(&ctx, &guild_id, false).await;
-      if let Some((channel, _)) = channel_by_name(&ctx, &channels, "log").await {
-        let title = format!("has left, {}", ai_text.as_str());
-        if let Err(why) = channel.send_message(&ctx, |m| m
-          .embed(|e| {
-            e.author(|a| a.icon_url(&user.face()).name(&user.name))
-              .title(title)
-              .timestamp(chrono::Utc::now().to_rfc3339())
-            })).await {
-          error!("Failed to log leaving user {:?}", why);
-        }
+      let title = format!("has left, {}", ai_text.as_str());
+      if let Err(why) = channel.send_message(&ctx, |m| m
+        .embed(|e| {
+          e.author(|a| a.icon_url(&user.face()).name(&user.name))
+            .title(title)
+            .timestamp(chrono::Utc::now().to_rfc3339())
+          })).await {
+        error!("Failed to log leaving user {:?}", why);
       }
     }
   }
@@ -168,9 +170,22 @@ impl EventHandler for Handler {
       }
     } else {
       if let Some(guild) = msg.guild(&ctx).await {
-        let mentioned_bot = (&msg.mentions).into_iter().any(|u| u.bot) || msg.content.starts_with("~");
+        let mentioned_bot = (&msg.mentions).into_iter().any(|u| u.bot) || msg.content.starts_with(self.options.prefix.as_str());
         if !mentioned_bot {
-          points::add_points(guild.id.as_u64().clone(), msg.author.id.as_u64().clone(), 1).await;
+          set!{ guild_u64 = guild.id.as_u64().clone()
+              , author_u64 = msg.author.id.as_u64().clone() };
+          let multiplier = multipliers::get_multiplier(guild_u64, msg.channel_id.as_u64().clone())
+                                        .await.unwrap_or(1);
+          let rules = antifarm::get_rules(guild_u64).await.unwrap_or_default();
+          if multiplier > 0
+          && antifarm::qualifies( &rules, guild_u64, author_u64
+                                , msg.content.as_str(), !msg.attachments.is_empty() ) {
+            match points::add_points(guild_u64, author_u64, multiplier, ledger::Reason::Chat).await {
+              Ok(Some(change)) => tiers::apply(&ctx, &guild.id, &change).await,
+              Ok(None) => (),
+              Err(why) => error!("Failed to add points {:?}", why)
+            }
+          }
           let is_admin =
             if let Some(member) = msg.member(&ctx.cache).await {
               if let Ok(permissions) = member.permissions(&ctx.cache).await {
@@ -203,8 +218,10 @@ impl EventHandler for Handler {
               if let Some(ch) = msg.channel(&ctx).await {
                 ch.id().name(&ctx).await.unwrap_or(String::from(""))
               } else { String::from("") };
-            if AI_ALLOWED.into_iter().any(|&c| c == channel_name.as_str()) {
-              let activity_level = chain::ACTIVITY_LEVEL.load(Ordering::Relaxed);
+            let guild_settings = settings::get(guild.id.as_u64().clone()).await;
+            if guild_settings.is_chat_channel(msg.channel_id.as_u64().clone(), channel_name.as_str()) {
+              let activity_level = guild_settings.activity
+                .unwrap_or(chain::ACTIVITY_LEVEL.load(Ordering::Relaxed));
               let rnd = rand::thread_rng().gen_range(0, activity_level);
               if rnd == 1 {
                 chain::chat(&ctx, &msg).await;
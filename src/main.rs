@@ -8,11 +8,11 @@ mod commands;
 mod stains;
 mod handler;
 mod amadeus;
+mod cli;
 
 #[tokio::main(core_threads=8)]
 async fn main() {
-  let conf = common::conf::parse_config();
-  if let Err(err) = amadeus::run(&conf).await {
+  if let Err(err) = cli::run().await {
     panic!("Amadeus died {:?}", err)
   }
 }
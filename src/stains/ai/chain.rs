@@ -1,10 +1,11 @@
 use crate::{
   common::{
+    settings,
+    db::{ self, DB, Record, keys::{ Key, Table } },
     help::lang,
     msg::{ reply, channel_message }
   },
-  collections::base::{ CONFUSION, CONFUSION_RU, OBFUSCATION, OBFUSCATION_RU },
-  collections::channels::AI_LEARN
+  collections::base::{ CONFUSION, CONFUSION_RU, OBFUSCATION, OBFUSCATION_RU }
 };
 
 use serenity::{
@@ -31,6 +32,7 @@ use chrono::{ Duration, Utc, DateTime };
 use tokio::sync::{ Mutex, MutexGuard };
 
 static CACHE_MAX : u64 = 15000;
+static CORPUS_MAX : usize = 50000;
 pub static ACTIVITY_LEVEL : AtomicU32 = AtomicU32::new(66);
 
 lazy_static! {
@@ -39,6 +41,39 @@ lazy_static! {
   pub static ref LAST_UPDATE: Mutex<DateTime<Utc>>  = Mutex::new(Utc::now());
 }
 
+/// Lines from `train-chain`, oldest are dropped after `CORPUS_MAX`
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Corpus {
+  pub lines: Vec<String>
+}
+
+impl Record for Corpus {}
+
+/// adds lines to corpus, returns corpus size
+pub async fn store_corpus(lines: Vec<String>) -> Result<usize, db::Error> {
+  DB.transaction(move |tx| -> Result<usize, db::Error> {
+    let key = Key::table(Table::Corpus, 0);
+    let mut corpus: Corpus = tx.load(&key)?.unwrap_or_default();
+    corpus.lines.extend(lines.into_iter().filter(|l| !l.trim().is_empty()));
+    if corpus.lines.len() > CORPUS_MAX {
+      let extra = corpus.lines.len() - CORPUS_MAX;
+      corpus.lines.drain(..extra);
+    }
+    tx.store(key, &corpus)?;
+    Ok(corpus.lines.len())
+  }).await
+}
+
+async fn load_corpus() -> Corpus {
+  match DB.transaction(|tx| tx.load::<Corpus>(&Key::table(Table::Corpus, 0))).await {
+    Ok(corpus) => corpus.unwrap_or_default(),
+    Err(why) => {
+      error!("Failed to load corpus {:?}", why);
+      Corpus::default()
+    }
+  }
+}
+
 pub async fn update_cache(ctx: &Context, guild_id: &GuildId) {
   if let Ok(channels) = guild_id.channels(&ctx).await {
     info!("updating ai chain has started");
@@ -49,9 +84,10 @@ pub async fn update_cache(ctx: &Context, guild_id: &GuildId) {
       *cache_ru = Chain::new();
     }
     let re = Regex::new(r"<@!?\d{15,20}>").unwrap();
+    let guild_settings = settings::get(*guild_id.as_u64()).await;
     for (chan, _) in channels {
       if let Some(c_name) = chan.name(&ctx).await {
-        if AI_LEARN.into_iter().any(|&c| c == c_name.as_str()) {
+        if guild_settings.is_learn_channel(*chan.as_u64(), c_name.as_str()) {
           if let Ok(messages) = chan.messages(&ctx, |r|
             r.limit(CACHE_MAX)
           ).await {
@@ -81,6 +117,13 @@ pub async fn update_cache(ctx: &Context, guild_id: &GuildId) {
         }
       }
     }
+    for line in load_corpus().await.lines {
+      if lang::is_russian(line.as_str()) {
+        cache_ru.feed_str(line.as_str());
+      } else {
+        cache_eng.feed_str(line.as_str());
+      }
+    }
     for confuse in CONFUSION_RU {
       cache_ru.feed_str( confuse );
     }
@@ -107,10 +150,11 @@ pub async fn make_quote(ctx: &Context, msg : &Message, author_id: UserId, limit:
     let mut chain = Chain::new();
     let re = Regex::new(r"<@!?\d{15,20}>").unwrap();
     let guild_id = guild.id;
+    let guild_settings = settings::get(*guild_id.as_u64()).await;
     if let Ok(channels) = guild_id.channels(&ctx).await {
       for (chan, _) in channels {
         if let Some(c_name) = chan.name(&ctx).await {
-          if AI_LEARN.into_iter().any(|&c| c == c_name.as_str()) {
+          if guild_settings.is_learn_channel(*chan.as_u64(), c_name.as_str()) {
             if let Ok(messages) = chan.messages(&ctx, |r|
               r.limit(limit)
             ).await {
@@ -0,0 +1,233 @@
+/*
+* History of tracked games
+* every finished game of roster players is kept by day it ended
+* for daily and weekly digests, old days are forgotten
+*/
+
+use crate::{
+  common::{
+    db::{ self, DB, Record, keys::{ self, Key } },
+    types::Player,
+    settings, state::{ self, Slot }
+  },
+  stains::cyber::{
+    types::Match,
+    utils::get_map
+  }
+};
+
+use serenity::{
+  prelude::*,
+  model::id::{ ChannelId, GuildId }
+};
+
+use comfy_table::*;
+use chrono::{ Datelike, Utc, Weekday };
+
+use std::collections::HashMap;
+
+/// days of history kept
+static HISTORY_DAYS : u64 = 60;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Played {
+  pub battletag: String,
+  pub discord: u64,
+  pub won: bool,
+  pub mmr_gain: i32,
+  /// highest MMR in other teams
+  pub opponent_mmr: u32,
+  pub map: String,
+  pub game_mode: u32,
+  /// unix time game was seen finished
+  pub ended: i64
+}
+
+impl Record for Vec<Played> {}
+
+/// game of every roster player in finished match
+pub fn played(m: &Match, players: &[Player]) -> Vec<Played> {
+  let ended = Utc::now().timestamp();
+  let mut out = Vec::new();
+  for (i, team) in m.teams.iter().enumerate() {
+    // opponents are everyone in other teams
+    let opponent_mmr = m.teams.iter().enumerate()
+      .filter(|(j, _)| *j != i)
+      .flat_map(|(_, t)| t.players.iter().map(|p| p.oldMmr))
+      .max().unwrap_or(0);
+    for tp in &team.players {
+      if let Some(player) = players.iter().find(|p| p.is(&tp.battleTag)) {
+        out.push(Played {
+          battletag: tp.battleTag.clone(),
+          discord: player.discord,
+          won: team.won || tp.won,
+          mmr_gain: tp.mmrGain,
+          opponent_mmr: opponent_mmr,
+          map: m.map.clone(),
+          game_mode: m.gameMode,
+          ended: ended
+        });
+      }
+    }
+  }
+  out
+}
+
+fn today() -> u64 {
+  (Utc::now().timestamp() / (24 * 60 * 60)) as u64
+}
+
+pub async fn record(played: Vec<Played>) {
+  if played.is_empty() {
+    return;
+  }
+  let day = today();
+  if let Err(why) = DB.transaction(move |tx| -> Result<(), db::Error> {
+    let key = Key::table(keys::Table::History, day);
+    let mut games: Vec<Played> = tx.load(&key)?.unwrap_or_default();
+    games.extend(played);
+    tx.store(key, &games)
+  }).await {
+    error!("Failed to record played games {:?}", why);
+  }
+}
+
+/// games of last `days` full days, today isn't included
+pub async fn last_days(days: u64) -> Result<Vec<Played>, db::Error> {
+  let today = today();
+  DB.transaction(move |tx| -> Result<Vec<Played>, db::Error> {
+    let mut games = Vec::new();
+    for day in today.saturating_sub(days)..today {
+      if let Some(day_games) = tx.load::<Vec<Played>>(&Key::table(keys::Table::History, day))? {
+        games.extend(day_games);
+      }
+    }
+    Ok(games)
+  }).await
+}
+
+/// forgets days older than `HISTORY_DAYS`, returns how many days were removed
+pub async fn purge() -> Result<usize, db::Error> {
+  let oldest = today().saturating_sub(HISTORY_DAYS);
+  DB.transaction(move |tx| -> Result<usize, db::Error> {
+    let old = tx.range(&Key::table(keys::Table::History, 0), &Key::table(keys::Table::History, oldest))?;
+    let removed = old.len();
+    for key in old {
+      tx.delete(key);
+    }
+    Ok(removed)
+  }).await
+}
+
+#[derive(Default)]
+struct Member {
+  name: String,
+  games: u32,
+  wins: u32,
+  mmr: i32
+}
+
+/// Digest of games: table of members, best win and most played maps
+/// `None` if nobody played
+pub fn digest(games: &[Played]) -> Option<(String, String, String)> {
+  if games.is_empty() {
+    return None;
+  }
+  let mut members: HashMap<&str, Member> = HashMap::new();
+  let mut maps: HashMap<&str, u32> = HashMap::new();
+  for game in games {
+    let member = members.entry(game.battletag.as_str()).or_default();
+    member.name = game.battletag.split("#").next().unwrap_or("").to_string();
+    member.games += 1;
+    if game.won { member.wins += 1; }
+    member.mmr += game.mmr_gain;
+    *maps.entry(game.map.as_str()).or_insert(0) += 1;
+  }
+  let mut members = members.into_iter().map(|(_, m)| m).collect::<Vec<Member>>();
+  members.sort_by(|a, b| b.games.cmp(&a.games).then(b.mmr.cmp(&a.mmr)));
+
+  let mut table = Table::new();
+  table.load_preset(presets::ASCII_MARKDOWN)
+       .set_content_arrangement(ContentArrangement::Dynamic)
+       .set_table_width(50)
+       .set_header(vec!["Player", "Games", "W", "L", "MMR"]);
+  for m in &members {
+    table.add_row(vec![
+      Cell::new(&m.name),
+      Cell::new(m.games).set_alignment(CellAlignment::Right),
+      Cell::new(m.wins).set_alignment(CellAlignment::Right),
+      Cell::new(m.games - m.wins).set_alignment(CellAlignment::Right),
+      Cell::new(format!("{:+}", m.mmr)).set_alignment(CellAlignment::Right)
+    ]);
+  }
+
+  let best_win = match games.iter().filter(|g| g.won).max_by_key(|g| g.opponent_mmr) {
+    Some(g) => format!("<@{}> beat **{}** MMR on {}", g.discord, g.opponent_mmr, get_map(g.map.as_str())),
+    None => String::from("no wins")
+  };
+
+  let mut maps = maps.into_iter().collect::<Vec<(&str, u32)>>();
+  maps.sort_by(|(_, a), (_, b)| b.cmp(a));
+  let maps = maps.into_iter().take(3)
+                 .map(|(map, count)| format!("{}: **{}**", get_map(map), count))
+                 .collect::<Vec<String>>()
+                 .join("\n");
+
+  Some((format!("```\n{}\n```", table), best_win, maps))
+}
+
+async fn post_digest(ctx: &Context, channel: &ChannelId, title: &str, days: u64) {
+  let games = match last_days(days).await {
+    Ok(games) => games,
+    Err(why) => {
+      error!("Failed to load history of games {:?}", why);
+      return;
+    }
+  };
+  if let Some((members, best_win, maps)) = digest(&games) {
+    if let Err(why) = channel.send_message(ctx, |m| m
+      .embed(|e| e
+        .title(title)
+        .description(members)
+        .fields(vec![ ("Best win", best_win, false)
+                    , ("Most played maps", maps, false) ])
+        .footer(|f| f.text(format!("{} games", games.len())))
+      )
+    ).await {
+      error!("Failed to post {} {:?}", title, why);
+    }
+  }
+}
+
+/// posts digest of yesterday once a day and of last week on Mondays
+pub async fn digests(ctx: &Context, guild_id: &GuildId) {
+  let day = today() as u32;
+  match state::load::<u32>(Slot::Digest).await {
+    Ok(Some(posted)) if posted >= day => return,
+    Ok(_) => (),
+    Err(why) => {
+      error!("Failed to load day of last digest {:?}", why);
+      return;
+    }
+  }
+  if let Some(channel) = settings::digest_channel(ctx, guild_id).await {
+    post_digest(ctx, &channel, "Daily digest", 1).await;
+    if Utc::now().weekday() == Weekday::Mon {
+      post_digest(ctx, &channel, "Weekly digest", 7).await;
+    }
+    if let Err(why) = state::store(Slot::Digest, day).await {
+      error!("Failed to remember day of digest {:?}", why);
+    }
+  }
+  match purge().await {
+    Ok(purged) if purged > 0 => info!("forgot {} days of games history", purged),
+    Ok(_) => (),
+    Err(why) => error!("Failed to purge games history {:?}", why)
+  }
+}
+
+/// seconds until next day starts (UTC)
+pub fn until_tomorrow() -> u64 {
+  let day = 24 * 60 * 60;
+  (day - Utc::now().timestamp() % day) as u64
+}
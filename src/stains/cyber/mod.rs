@@ -1,5 +1,8 @@
 pub mod types;
 pub mod utils;
+pub mod summary;
+pub mod history;
 pub mod team_checker;
 pub mod twitch;
-pub mod goodgame;
\ No newline at end of file
+pub mod goodgame;
+pub mod w3c;
\ No newline at end of file
@@ -0,0 +1,71 @@
+/*
+* Match summaries
+* same layout for any mode, 1v1 is just two teams of one and FFA is teams of one
+* used by live tracker and ~match
+*/
+
+use crate::stains::cyber::{
+  types::*,
+  utils::{ get_race2, get_map, get_mode, get_hero }
+};
+
+/// every player of match, teams could be empty or missing
+pub fn match_players(m: &Match) -> impl Iterator<Item = &TeamPlayer> {
+  m.teams.iter().flat_map(|t| t.players.iter())
+}
+
+pub fn live_description(m: &Match) -> String {
+  let teams = m.teams.iter()
+    .filter(|t| !t.players.is_empty())
+    .map(|t| t.players.iter()
+              .map(|p| format!("({}) **{}** [{}]", get_race2(p.race), p.name, p.oldMmr))
+              .collect::<Vec<String>>()
+              .join(" + "))
+    .collect::<Vec<String>>();
+  format!("{}\n\n{}, map: **{}**", teams.join("\n*vs*\n"), get_mode(m.gameMode), get_map(m.map.as_str()))
+}
+
+pub fn finished_description(m: &Match) -> String {
+  let teams = m.teams.iter()
+    .filter(|t| !t.players.is_empty())
+    .map(|t| {
+      let players = t.players.iter().map(|p|
+        if t.won {
+          format!("({}) __**{}**__ [{}] **{:+}**", get_race2(p.race), p.name, p.oldMmr, p.mmrGain)
+        } else {
+          format!("({}) __*{}*__ [{}] *{:+}*", get_race2(p.race), p.name, p.oldMmr, p.mmrGain)
+        }).collect::<Vec<String>>().join(" + ");
+      format!("{} {}", players, if t.won { "(won)" } else { "(lost)" })
+    }).collect::<Vec<String>>();
+  format!("{}\n\n{}, map: **{}**", teams.join("\n*vs*\n"), get_mode(m.gameMode), get_map(m.map.as_str()))
+}
+
+fn score_text(s: &PlayerScore) -> String {
+  let heroes = if s.heroes.is_empty() {
+      String::from("no heroes")
+    } else {
+      s.heroes.iter()
+              .map(|h| format!("{} {}", get_hero(h.icon.as_str()), h.level))
+              .collect::<Vec<String>>()
+              .join(", ")
+    };
+  format!("**{}**\nheroes killed: {}, items: {}, hero exp: {}\nproduced: {}, killed: {}, largest army: {}\ngold: {}, lumber: {}, upkeep lost: {}"
+    , heroes
+    , s.heroScore.heroesKilled
+    , s.heroScore.itemsObtained
+    , s.heroScore.expGained
+    , s.unitScore.unitsProduced
+    , s.unitScore.unitsKilled
+    , s.unitScore.largestArmy
+    , s.resourceScore.goldCollected
+    , s.resourceScore.lumberCollected
+    , s.resourceScore.goldUpkeepLost)
+}
+
+/// score of every player who has one, in order of teams
+pub fn score_fields(m: &Match, scores: &[PlayerScore]) -> Vec<(String, String, bool)> {
+  match_players(m).filter_map(|tp|
+    scores.iter().find(|s| s.battleTag.to_lowercase() == tp.battleTag.to_lowercase()).map(|s|
+      (tp.name.clone(), score_text(s), true))
+  ).collect()
+}
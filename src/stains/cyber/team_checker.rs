@@ -1,374 +1,346 @@
 use crate::{
-  collections::team::players,
-  common::points,
+  common::{
+    conf, points, tiers, ledger, roster,
+    db::{ self, DB, Record, keys::{ Key, Table } },
+    types::Player
+  },
   stains::cyber::{
-    types::*,
-    utils::{ get_race2, get_map }
-  }
+    types::*, w3c, history,
+    summary::{ match_players, live_description, finished_description, score_fields }
+  },
+  commands::pad::CURRENT_SEASON
 };
 
 use serenity::{
   prelude::*
 };
 
-use reqwest;
-
-use std::collections::HashMap;
+use std::{
+  borrow::Cow,
+  collections::HashMap,
+  sync::atomic::Ordering
+};
 use tokio::sync::{ Mutex, MutexGuard };
+use chrono::Utc;
+
+/// pages of player history searched for finished game
+static HISTORY_PAGES : u32 = 5;
+
+/// seconds game is tracked at most before it's given up
+pub const TRACKING_TIMEOUT : u64 = 666 * 30;
 
 lazy_static! {
   pub static ref GAMES: Mutex<HashMap<String, TrackingGame>> = Mutex::new(HashMap::new());
 }
 
-async fn check_match( matchid_lol : &str
-                    , btag: &str ) -> Option<(String, u32, bool, Option<(String, String, String, String)>)> {
+/// layout before tracking channel was remembered
+#[derive(Deserialize)]
+struct TrackingGameV1 {
+  tracking_msg_id: u64,
+  passed_time: u32,
+  still_live: bool,
+  player: Player
+}
 
-  let mut are_you_winning = false;
-  let mut matchid_s : String = String::new();
-  if let Ok(wtf) = reqwest::get("https://statistic-service.w3champions.com/api/matches?offset=0&gateway=20").await {
-    if let Ok(going) = wtf.json::<Going>().await {
-      for mm in &going.matches {
-        if mm.startTime == matchid_lol {
-          // TODO: change that hack one day
-          if
-            if mm.gameMode == 6 || mm.gameMode == 2 {
-              mm.teams[0].players[0].battleTag == btag || mm.teams[1].players[0].battleTag == btag ||
-              mm.teams[0].players[1].battleTag == btag || mm.teams[1].players[1].battleTag == btag
-            } else {
-              mm.teams[0].players[0].battleTag == btag || mm.teams[1].players[0].battleTag == btag
-            }
-          {
-            matchid_s = mm.id.clone();
-            break;
-          }
-        }
+/// layout with count of live checks instead of start time
+#[derive(Deserialize)]
+struct TrackingGameV2 {
+  tracking_msg_id: u64,
+  channel: u64,
+  passed_time: u32,
+  still_live: bool,
+  player: Player
+}
+
+/// start time from count of live checks, made every `live_check_interval`
+fn started_before(checks: u32) -> i64 {
+  Utc::now().timestamp() - (checks as u64 * conf::current().live_check_interval) as i64
+}
+
+impl Record for HashMap<String, TrackingGame> {
+  const VERSION: u8 = 3;
+  fn upgrade(version: u8, body: &[u8]) -> Result<Self, db::Error> {
+    match version {
+      1 => {
+        let old: HashMap<String, TrackingGameV1> = bincode::deserialize(body)?;
+        Ok(old.into_iter().map(|(k, g)|
+          (k, TrackingGame { tracking_msg_id: g.tracking_msg_id
+                           , channel: 0
+                           , started: started_before(g.passed_time)
+                           , still_live: g.still_live
+                           , player: g.player })
+        ).collect())
+      },
+      2 => {
+        let old: HashMap<String, TrackingGameV2> = bincode::deserialize(body)?;
+        Ok(old.into_iter().map(|(k, g)|
+          (k, TrackingGame { tracking_msg_id: g.tracking_msg_id
+                           , channel: g.channel
+                           , started: started_before(g.passed_time)
+                           , still_live: g.still_live
+                           , player: g.player })
+        ).collect())
+      },
+      v => Err(db::Error::Version(v))
+    }
+  }
+}
+
+/// seconds since game was found
+pub fn tracked_seconds(track: &TrackingGame) -> u64 {
+  (Utc::now().timestamp() - track.started).max(0) as u64
+}
+
+/// games tracked before restart
+pub async fn restore_games() -> HashMap<String, TrackingGame> {
+  match DB.transaction(|tx| tx.load(&Key::table(Table::Games, 0))).await {
+    Ok(games) => games.unwrap_or_default(),
+    Err(why) => {
+      error!("Failed to restore tracked games {:?}", why);
+      HashMap::new()
+    }
+  }
+}
+
+/// remembers tracked games so they are finished after restart
+pub async fn save_games(games: &HashMap<String, TrackingGame>) {
+  let games = games.clone();
+  if let Err(why) = DB.transaction(move |tx| -> Result<(), db::Error> {
+    tx.store(Key::table(Table::Games, 0), &games)
+  }).await {
+    error!("Failed to save tracked games {:?}", why);
+  }
+}
+
+/// first tracked player in match, with battletag spelled as on w3champions
+fn tracked_player(m: &Match, players: &[Player]) -> Option<Player> {
+  players.iter().find_map(|p|
+    match_players(m).find(|tp| p.is(&tp.battleTag)).map(|tp| {
+      let mut player = p.clone();
+      player.battletag = Cow::Owned(tp.battleTag.clone());
+      player
+    })
+  )
+}
+
+/// id of finished match started at `start_time` from player history
+/// history is paged back until matches started before it, so games finished
+/// long ago (e.g. during restart) are still found
+async fn find_match(start_time: &str, btag: &str, gateway: u32) -> Option<String> {
+  let season = CURRENT_SEASON.load(Ordering::Relaxed).to_string();
+  for page in 0..HISTORY_PAGES {
+    let going = match w3c::player_matches(btag, gateway, &season, page * w3c::MATCHES_PAGE).await {
+      Ok(going) => going,
+      Err(why) => {
+        warn!("Failed to get finished matches of {} {}", btag, why);
+        return None;
       }
+    };
+    if let Some(mm) = going.matches.iter().find(|mm| mm.startTime == start_time) {
+      return Some(mm.id.clone());
+    }
+    // newest first so older page can't have it (start times are ISO 8601)
+    let passed = going.matches.last().map(|mm| mm.startTime.as_str() < start_time).unwrap_or(true);
+    if passed || (page + 1) * w3c::MATCHES_PAGE >= going.count {
+      break;
     }
   }
+  None
+}
 
-  if matchid_s.is_empty() { return None; }
-  let matchid = matchid_s.as_str();
-  let url = format!("https://statistic-service.w3champions.com/api/matches/{}", matchid);
+async fn check_match( matchid_lol : &str
+                    , btag: &str
+                    , gateway: u32
+                    , players: &[Player] ) -> Option<FinishedGame> {
+  let matchid_s = find_match(matchid_lol, btag, gateway).await?;
 
-  if let Ok(res) = reqwest::get(url.as_str()).await {
-    match res.json::<MD>().await {
-      Ok(md) => {
-        let m = md.match_data;
-        let mstr_o =
-        if m.gameMode == 1 {
-          set!{ g_map = get_map(m.map.as_str())
-              , race1 = get_race2(m.teams[0].players[0].race)
-              , race2 = get_race2(m.teams[1].players[0].race) };
-          let player1 = if m.teams[0].players[0].won {
-            if m.teams[0].players[0].battleTag == btag {
-              are_you_winning = true;
-            }
-            format!("__**{}**__ **+{}**", m.teams[0].players[0].name, m.teams[0].players[0].mmrGain)
-          } else {
-            format!("__*{}*__ **{}**", m.teams[0].players[0].name, m.teams[1].players[0].mmrGain)
-          };
-          let player2 = if m.teams[1].players[0].won {
-            format!("__**{}**__ **+{}**", m.teams[1].players[0].name, m.teams[0].players[0].mmrGain)
-          } else {
-            format!("__*{}*__ **{}**", m.teams[1].players[0].name, m.teams[1].players[0].mmrGain)
-          };
-          Some(format!("({}) {} [{}] *vs* ({}) {} [{}] *{}*",
-              race1, player1, m.teams[0].players[0].oldMmr
-            , race2, player2, m.teams[1].players[0].oldMmr, g_map))
-        } else if m.gameMode == 6 || m.gameMode == 2 {
-          set!{ g_map = get_map(m.map.as_str())
-              , race1 = get_race2(m.teams[0].players[0].race)
-              , race12 = get_race2(m.teams[0].players[1].race)
-              , race2 = get_race2(m.teams[1].players[0].race)
-              , race22 = get_race2(m.teams[1].players[1].race) };
-          if m.teams[0].won {
-            if m.teams[0].players[0].battleTag == btag || m.teams[0].players[1].battleTag == btag {
-              are_you_winning = true;
-            }
-          }
-          if m.gameMode == 6 {
-            if m.teams[0].won {
-              Some(format!("({}+{}) __**{} + {} [{}]**__ **+{}** (won)\n*vs*\n({}+{}) __*{} + {} [{}]*__ *{}* (lost)\n\nmap: **{}**",
-                race1, race12, m.teams[0].players[0].name, m.teams[0].players[1].name, m.teams[0].players[0].oldMmr, m.teams[0].players[0].mmrGain
-              , race2, race22, m.teams[1].players[0].name, m.teams[1].players[1].name, m.teams[1].players[0].oldMmr, m.teams[1].players[0].mmrGain, g_map))
-            } else {
-              Some(format!("({}+{}) __*{} + {} [{}]*__ *{}* (lost)\n*vs*\n({}+{}) __**{} + {} [{}]**__ **+{}** (won)\n\nmap: **{}**",
-                race1, race12, m.teams[0].players[0].name, m.teams[0].players[1].name, m.teams[0].players[0].oldMmr, m.teams[0].players[0].mmrGain
-              , race2, race22, m.teams[1].players[0].name, m.teams[1].players[1].name, m.teams[1].players[0].oldMmr, m.teams[1].players[0].mmrGain, g_map))
-            }
-          } else {
-            if m.teams[0].won {
-              Some(format!("({}+{}) __**{} [{}]**__ **+{}** + __**{} [{}]**__ **+{}** (won)\n*vs*\n({}+{}) __*{} [{}]*__ *{}* + __*{} [{}]*__ *{}* (lost)\n\nmap: **{}**",
-                race1, race12, m.teams[0].players[0].name, m.teams[0].players[0].oldMmr, m.teams[0].players[0].mmrGain, m.teams[0].players[1].name, m.teams[0].players[1].oldMmr, m.teams[0].players[1].mmrGain
-              , race2, race22, m.teams[1].players[0].name, m.teams[1].players[0].oldMmr, m.teams[1].players[0].mmrGain, m.teams[1].players[1].name, m.teams[1].players[1].oldMmr, m.teams[1].players[1].mmrGain, g_map))
-            } else {
-              Some(format!("({}+{}) __*{} [{}]*__ *{}* + __*{} [{}]*__ *{}* (lost)\n*vs*\n({}+{}) __**{} [{}]**__ **+{}** + __**{} [{}]**__ **+{}** (won)\n\nmap: **{}**",
-              race1, race12, m.teams[0].players[0].name, m.teams[0].players[0].oldMmr, m.teams[0].players[0].mmrGain, m.teams[0].players[1].name, m.teams[0].players[1].oldMmr, m.teams[0].players[1].mmrGain
-              , race2, race22, m.teams[1].players[0].name, m.teams[1].players[0].oldMmr, m.teams[1].players[0].mmrGain, m.teams[1].players[1].name, m.teams[1].players[1].oldMmr, m.teams[1].players[1].mmrGain, g_map))
-            }
-          }
-        } else {
-          None
-        };
-        match mstr_o {
-          Some(mstr) => {
-            let duration_in_minutes = m.durationInSeconds / 60;
-            if md.playerScores.len() > 1 && m.gameMode == 1 {
-              set! { p1 = &md.playerScores[0]
-                   , p2 = &md.playerScores[1]
-                   , s1 = p1.battleTag.clone()
-                   , s2 = p2.battleTag.clone() };
-              let s3 = format!("produced: {}\nkilled: {}\ngold: {}\nhero exp: {}"
-                  , p1.unitScore.unitsProduced
-                  , p1.unitScore.unitsKilled
-                  , p1.resourceScore.goldCollected
-                  , p1.heroScore.expGained);
-              let s4 = format!("produced: {}\nkilled: {}\ngold: {}\nhero exp: {}"
-                  , p2.unitScore.unitsProduced
-                  , p2.unitScore.unitsKilled
-                  , p2.resourceScore.goldCollected
-                  , p2.heroScore.expGained);
-              let scores = if m.teams[0].players[0].battleTag == s1 {
-                  Some((s1,s2,s3,s4))
-                } else {
-                  Some((s2,s1,s4,s3))
-                };
-              return Some((mstr, duration_in_minutes, are_you_winning, scores));
-            }
-            return Some((mstr, duration_in_minutes, are_you_winning,  None));
-          }, None => {
-            return None;
-          }
-        }
-      }, Err(err) => {
-        error!("Failed parse MD {:?}", err);
+  match w3c::match_details(matchid_s.as_str()).await {
+    Ok(md) => {
+      let m = md.match_data;
+      if match_players(&m).next().is_none() {
+        warn!("match {} has no players", matchid_s);
+        return None;
       }
+      let win = m.teams.iter().any(|t|
+        t.players.iter().any(|p| p.battleTag.to_lowercase() == btag.to_lowercase() && (t.won || p.won))
+      );
+      return Some(FinishedGame {
+        description: finished_description(&m),
+        passed_time: m.durationInSeconds / 60,
+        win: win,
+        additional_fields: score_fields(&m, &md.playerScores),
+        played: history::played(&m, players)
+      });
+    }, Err(err) => {
+      error!("Failed to get match {} {}", matchid_s, err);
     }
   }
   None
 }
 
+/// `channel_id` is current log channel, games keep channel they were posted to
 pub async fn check<'a>( ctx: &Context
                       , channel_id: u64
                       , games_lock: &mut MutexGuard<'a, HashMap<String, TrackingGame>>
                       ) -> Vec<StartingGame> {
   let mut out : Vec<StartingGame> = Vec::new();
-  if let Ok(res) =
-    // getaway 20 = Europe
-    reqwest::get("https://statistic-service.w3champions.com/api/matches/ongoing?offset=0&gateway=20").await {
-    if let Ok(going) = res.json::<Going>().await {
-      if going.matches.len() > 0 {
-        for m in going.matches {
-          if m.gameMode == 1 {
-            if m.teams.len() > 1 && m.teams[0].players.len() > 0 && m.teams[1].players.len() > 0 {
-              if let Some(playa) = players().into_iter().find(|p|
-                   m.teams[0].players[0].battleTag == p.battletag
-                || m.teams[1].players[0].battleTag == p.battletag
-              ) {
-
-                let g_map = get_map(m.map.as_str());
-                let race1 = get_race2(m.teams[0].players[0].race);
-                let race2 = get_race2(m.teams[1].players[0].race);
-                let mstr = format!("({}) **{}** [{}] *vs* ({}) **{}** [{}] *{}*",
-                  race1, m.teams[0].players[0].name, m.teams[0].players[0].oldMmr
-                , race2, m.teams[1].players[0].name, m.teams[1].players[0].oldMmr, g_map);
-
-                if let Some(track) = games_lock.get_mut(m.startTime.as_str()) {
-                  track.still_live = true;
-                  let minutes = track.passed_time / 2;
-                  let footer = format!("Passed: {} min", minutes);
-
-                  if let Ok(mut msg) = ctx.http.get_message(channel_id, track.tracking_msg_id).await {
-                    if let Ok(user) = ctx.http.get_user(playa.discord).await {
-
-                      let mut fields = Vec::new();
-                      let mut img = None;
-                      let mut url = None;
-                      if msg.embeds.len() > 0 && msg.embeds[0].fields.len() > 0 {
-                        for f in msg.embeds[0].fields.clone() {
-                          fields.push((f.name, f.value, f.inline));
-                        }
-                        img = msg.embeds[0].image.clone();
-                        url = msg.embeds[0].url.clone();
-                      };
-
-                      if let Err(why) = msg.edit(ctx, |m| m
-                        .embed(|e|  {
-                          let mut e = e
-                            .title("LIVE")
-                            .author(|a| a.icon_url(&user.face()).name(&user.name))
-                            .description(mstr)
-                            .footer(|f| f.text(footer));
-                          if fields.len() > 0 {
-                            e = e.fields(fields);
-                          }
-                          if img.is_some() {
-                            e = e.image(img.unwrap().url);
-                          }
-                          if url.is_some() {
-                            e = e.url(url.unwrap());
-                          }
-                          e
-                        }
-                      )).await {
-                        error!("Failed to post live match {:?}", why);
-                      }
-                    }
-                  }
+  let players = roster::players().await;
+  // only gateways someone plays on
+  let mut gateways = players.iter().map(|p| p.gateway()).collect::<Vec<u32>>();
+  gateways.sort();
+  gateways.dedup();
+  let mut matches : Vec<Match> = Vec::new();
+  for gateway in gateways {
+    match w3c::ongoing(gateway, None).await {
+      Ok(going) => matches.extend(going.matches),
+      Err(why) => error!("Failed to get ongoing matches of gateway {} {}", gateway, why)
+    }
+  }
+  if matches.len() > 0 {
+    for m in matches {
+      if let Some(playa) = tracked_player(&m, &players) {
+        let mstr = live_description(&m);
 
-                } else {
-                  out.push(
-                    StartingGame {
-                      key: m.startTime,
-                      description: mstr,
-                      player: playa
-                    }
-                  );
+        if let Some(track) = games_lock.get_mut(m.startTime.as_str()) {
+          track.still_live = true;
+          set!{ minutes = tracked_seconds(track) / 60
+              , footer = format!("Passed: {} min", minutes) };
+          if let Ok(mut msg) = ctx.http.get_message(track.channel_or(channel_id), track.tracking_msg_id).await {
+            if let Ok(user) = ctx.http.get_user(playa.discord).await {
+              setm!{ fields = Vec::new()
+                   , img    = None
+                   , url    = None };
+              if msg.embeds.len() > 0 && msg.embeds[0].fields.len() > 0 {
+                for f in msg.embeds[0].fields.clone() {
+                  fields.push((f.name, f.value, f.inline));
                 }
-              }
-            }
-          } else if m.gameMode == 6 || m.gameMode == 2 { // AT or RT mode
-            if m.teams.len() > 1 && m.teams[0].players.len() > 1 && m.teams[1].players.len() > 1 {
-              if let Some(playa) = players().into_iter().find(|p|
-                   m.teams[0].players[0].battleTag == p.battletag
-                || m.teams[1].players[0].battleTag == p.battletag
-                || m.teams[0].players[1].battleTag == p.battletag
-                || m.teams[1].players[1].battleTag == p.battletag) {
-
-                let g_map = get_map(m.map.as_str());
-
-                set! { race1  = get_race2(m.teams[0].players[0].race)
-                     , race12 = get_race2(m.teams[0].players[1].race)
-                     , race2  = get_race2(m.teams[1].players[0].race)
-                     , race22 = get_race2(m.teams[1].players[1].race) };
-
-                let mstr = if m.gameMode == 6 {
-                  format!("({}+{}) **{}** + **{}** [{}]\n*vs*\n({}+{}) **{}** + **{}** [{}]\n\nmap: **{}**",
-                    race1, race12, m.teams[0].players[0].name, m.teams[0].players[1].name, m.teams[0].players[0].oldMmr
-                  , race2, race22, m.teams[1].players[0].name, m.teams[1].players[1].name, m.teams[1].players[0].oldMmr, g_map)
-                } else {
-                  format!("({}+{}) **{}** [{}] + **{}** [{}]\n*vs*\n({}+{}) **{}** [{}] + **{}** [{}]\n\nmap: **{}**",
-                    race1, race12, m.teams[0].players[0].name, m.teams[0].players[0].oldMmr, m.teams[0].players[1].name, m.teams[0].players[1].oldMmr
-                  , race2, race22, m.teams[1].players[0].name, m.teams[0].players[0].oldMmr, m.teams[1].players[1].name, m.teams[1].players[1].oldMmr, g_map)
-                };
+                img = msg.embeds[0].image.clone();
+                url = msg.embeds[0].url.clone();
+              };
 
-                if let Some(track) = games_lock.get_mut(m.startTime.as_str()) {
-                  track.still_live = true;
-                  set!{ minutes = track.passed_time / 2
-                      , footer = format!("Passed: {} min", minutes) };
-                  if let Ok(mut msg) = ctx.http.get_message(channel_id, track.tracking_msg_id).await {
-                    if let Ok(user) = ctx.http.get_user(playa.discord).await {
-                      setm!{ fields = Vec::new()
-                           , img    = None
-                           , url    = None };
-                      if msg.embeds.len() > 0 && msg.embeds[0].fields.len() > 0 {
-                        for f in msg.embeds[0].fields.clone() {
-                          fields.push((f.name, f.value, f.inline));
-                        }
-                        img = msg.embeds[0].image.clone();
-                        url = msg.embeds[0].url.clone();
-                      };
-
-                      if let Err(why) = msg.edit(ctx, |m| m
-                        .embed(|e| {
-                          let mut e = e
-                            .title("LIVE")
-                            .author(|a| a.icon_url(&user.face()).name(&user.name))
-                            .description(mstr)
-                            .footer(|f| f.text(footer));
-                          if fields.len() > 0 {
-                            e = e.fields(fields);
-                          }
-                          if img.is_some() {
-                            e = e.image(img.unwrap().url);
-                          }
-                          if url.is_some() {
-                            e = e.url(url.unwrap());
-                          }
-                          e
-                        }
-                      )).await {
-                        error!("Failed to post live match {:?}", why);
-                      }
-                    }
+              if let Err(why) = msg.edit(ctx, |m| m
+                .embed(|e| {
+                  let mut e = e
+                    .title("LIVE")
+                    .author(|a| a.icon_url(&user.face()).name(&user.name))
+                    .description(mstr)
+                    .footer(|f| f.text(footer));
+                  if fields.len() > 0 {
+                    e = e.fields(fields);
                   }
-                } else {
-                  out.push(
-                    StartingGame {
-                      key: m.startTime,
-                      description: mstr,
-                      player: playa
-                    }
-                  );
+                  if img.is_some() {
+                    e = e.image(img.unwrap().url);
+                  }
+                  if url.is_some() {
+                    e = e.url(url.unwrap());
+                  }
+                  e
                 }
-
+              )).await {
+                error!("Failed to post live match {:?}", why);
               }
             }
           }
+        } else {
+          out.push(
+            StartingGame {
+              key: m.startTime,
+              description: mstr,
+              player: playa
+            }
+          );
         }
+      }
+    }
 
-        let mut k_to_del : Vec<String> = Vec::new();
-        for (k, track) in games_lock.iter_mut() {
-          if !track.still_live {
-            if let Some((new_text, duration, win, fields)) = check_match(k, track.player.battletag).await {
-              if let Ok(mut msg) = ctx.http.get_message(channel_id, track.tracking_msg_id).await {
-                let footer : String = format!("Passed: {} min", duration);
-                if let Ok(user) = ctx.http.get_user(track.player.discord).await {
-                  let mut old_fields = Vec::new();
-                  let mut url = None;
-                  if msg.embeds.len() > 0 && msg.embeds[0].fields.len() > 0 {
-                    for f in msg.embeds[0].fields.clone() {
-                      old_fields.push((f.name, f.value, f.inline));
-                    }
-                    url = msg.embeds[0].url.clone();
-                  };
-                  if let Err(why) = msg.edit(ctx, |m| m
-                    .embed(|e| {
-                      let mut e =
-                        e.author(|a| a.icon_url(&user.face()).name(&user.name))
-                        .title("FINISHED")
-                        .description(new_text)
-                        .footer(|f| f.text(footer));
-                      if old_fields.len() > 0 {
-                        e = e.fields(old_fields);
-                      }
-                      if fields.is_some() {
-                        let (s1,s2,s3,s4) = fields.unwrap();
-                        e = e.fields(vec![
-                          (s1, s3, true),
-                          (s2, s4, true)
-                        ]);
-                      }
-                      if url.is_some() {
-                        e = e.url(url.unwrap());
-                      }
-                      e
-                    })
-                  ).await {
-                    error!("Failed to update live match {:?}", why);
-                  } else {
-                    if win {
-                      if let Some(guild_id) = msg.guild_id {
-                        points::add_points( guild_id.as_u64().clone()
-                                          , track.player.discord, 10 ).await;
-                      }
+    let mut k_to_del : Vec<String> = Vec::new();
+    for (k, track) in games_lock.iter_mut() {
+      if !track.still_live {
+        if let Some(finished) = check_match(k, &track.player.battletag, track.player.gateway(), &players).await {
+          let FinishedGame { description, passed_time, win, additional_fields, played } = finished;
+          history::record(played).await;
+          if let Ok(mut msg) = ctx.http.get_message(track.channel_or(channel_id), track.tracking_msg_id).await {
+            let footer : String = format!("Passed: {} min", passed_time);
+            if let Ok(user) = ctx.http.get_user(track.player.discord).await {
+              let mut old_fields = Vec::new();
+              let mut url = None;
+              if msg.embeds.len() > 0 && msg.embeds[0].fields.len() > 0 {
+                for f in msg.embeds[0].fields.clone() {
+                  old_fields.push((f.name, f.value, f.inline));
+                }
+                url = msg.embeds[0].url.clone();
+              };
+              if let Err(why) = msg.edit(ctx, |m| m
+                .embed(|e| {
+                  let mut e =
+                    e.author(|a| a.icon_url(&user.face()).name(&user.name))
+                    .title("FINISHED")
+                    .description(description)
+                    .footer(|f| f.text(footer));
+                  if old_fields.len() > 0 {
+                    e = e.fields(old_fields);
+                  }
+                  if additional_fields.len() > 0 {
+                    e = e.fields(additional_fields);
+                  }
+                  if url.is_some() {
+                    e = e.url(url.unwrap());
+                  }
+                  e
+                })
+              ).await {
+                error!("Failed to update live match {:?}", why);
+              } else {
+                if win {
+                  if let Some(guild_id) = msg.guild_id {
+                    match points::add_points( guild_id.as_u64().clone()
+                                            , track.player.discord, 10
+                                            , ledger::Reason::Game ).await {
+                      Ok(Some(change)) => tiers::apply(ctx, &guild_id, &change).await,
+                      Ok(None) => (),
+                      Err(why) => error!("Failed to add win points {:?}", why)
                     }
                   }
                 }
               }
-              // we only delete match if it's passed
-              // if not possibly there is a bug and we're waiting for end
-              k_to_del.push(k.clone());
             }
           }
+          // we only delete match if it's passed
+          // if not possibly there is a bug and we're waiting for end
+          k_to_del.push(k.clone());
         }
-
-        for ktd in k_to_del {
-          games_lock.remove(ktd.as_str());
-        }
-
       }
     }
+
+    for ktd in k_to_del {
+      games_lock.remove(ktd.as_str());
+    }
+
   }
   out
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn upgrades_check_count_to_start_time() {
+    let player = Player { battletag: Cow::Borrowed("Foo#1234")
+                        , discord: 5
+                        , streams: None
+                        , gateway: 0 };
+    // same bincode layout as `TrackingGameV2`
+    let mut old = HashMap::new();
+    old.insert(String::from("match"), (7u64, 9u64, 10u32, true, player));
+    let mut bytes = b"AMDS".to_vec();
+    bytes.push(2);
+    bytes.extend(bincode::serialize(&old).unwrap());
+    let games: HashMap<String, TrackingGame> = db::decode(&bytes).unwrap();
+    let game = &games["match"];
+    assert_eq!(game.tracking_msg_id, 7);
+    assert_eq!(game.channel, 9);
+    let expected = 10 * conf::current().live_check_interval;
+    assert!(tracked_seconds(game) >= expected && tracked_seconds(game) <= expected + 5);
+  }
+}
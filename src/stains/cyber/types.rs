@@ -1,4 +1,5 @@
 use crate::common::types::Player;
+use crate::stains::cyber::history::Played;
 use std::collections::HashMap;
 
 pub_struct!(Stats {
@@ -161,18 +162,29 @@ pub_struct!(PlayerScore {
 pub struct MD {
   #[serde(rename = "match")]
   pub match_data: Match,
+  #[serde(default)]
   pub playerScores: Vec<PlayerScore>
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TrackingGame {
   pub tracking_msg_id: u64,
-  pub passed_time: u32,
+  /// channel of tracking message, 0 for games tracked before it was remembered
+  pub channel: u64,
+  /// unix time game was found
+  pub started: i64,
   pub still_live: bool,
   pub player: Player
 }
 
+impl TrackingGame {
+  /// games tracked before channel was remembered were posted to log channel
+  pub fn channel_or(&self, log_channel: u64) -> u64 {
+    if self.channel != 0 { self.channel } else { log_channel }
+  }
+}
+
 #[allow(non_snake_case)]
 #[derive(Debug)]
 pub struct StartingGame {
@@ -181,6 +193,18 @@ pub struct StartingGame {
   pub player: Player
 }
 
+#[derive(Debug)]
+pub struct FinishedGame {
+  pub description: String,
+  /// minutes
+  pub passed_time: u32,
+  /// tracked player's team won
+  pub win: bool,
+  pub additional_fields: Vec<(String, String, bool)>,
+  /// games of roster players for history
+  pub played: Vec<Played>
+}
+
 pub_struct!(Season {
   id: u32,
 });
@@ -22,6 +22,74 @@ pub fn get_race2(r : u32) -> String {
   )
 }
 
+/// hero name by icon of w3champions
+pub fn get_hero(icon: &str) -> String {
+  String::from(
+    match icon {
+      "archmage"            => "Archmage",
+      "mountainking"        => "Mountain King",
+      "paladin"             => "Paladin",
+      "sorceror"            => "Blood Mage",
+      "bloodmage"           => "Blood Mage",
+      "blademaster"         => "Blademaster",
+      "farseer"             => "Far Seer",
+      "taurenchieftain"     => "Tauren Chieftain",
+      "shadowhunter"        => "Shadow Hunter",
+      "demonhunter"         => "Demon Hunter",
+      "keeperofthegrove"    => "Keeper of the Grove",
+      "priestessofthemoon"  => "Priestess of the Moon",
+      "warden"              => "Warden",
+      "deathknight"         => "Death Knight",
+      "dreadlord"           => "Dreadlord",
+      "lich"                => "Lich",
+      "cryptlord"           => "Crypt Lord",
+      "alchemist"           => "Alchemist",
+      "avatarofflame"       => "Firelord",
+      "bansheeranger"       => "Dark Ranger",
+      "beastmaster"         => "Beastmaster",
+      "pandarenbrewmaster"  => "Brewmaster",
+      "pitlord"             => "Pit Lord",
+      "seawitch"            => "Naga Sea Witch",
+      "tinker"              => "Tinker",
+      other                 => other
+    }
+  )
+}
+
+pub fn get_mode(m: u32) -> String {
+  String::from(
+    match m {
+      1 => "1v1",
+      2 => "2v2 RT",
+      4 => "4v4",
+      5 => "FFA",
+      6 => "2v2 AT",
+      _ => "Unknown mode"
+    }
+  )
+}
+
+/// gateway by name or number, `None` for unknown
+pub fn get_gateway(g: &str) -> Option<u32> {
+  match g.to_lowercase().as_str() {
+    "10" | "us" | "america" => Some(10),
+    "20" | "eu" | "europe"  => Some(20),
+    "30" | "asia"           => Some(30),
+    _ => None
+  }
+}
+
+pub fn get_gateway_name(g: u32) -> String {
+  String::from(
+    match g {
+      10 => "America",
+      20 => "Europe",
+      30 => "Asia",
+      _ => "Unknown"
+    }
+  )
+}
+
 pub fn get_league(l: u32) -> String {
   String::from(match l {
     0 => "GrandMaster",
@@ -0,0 +1,307 @@
+/*
+* W3Champions statistic service client
+* everything going to w3champions passes here so it's rate limited together,
+* failed requests are retried with backoff and fresh responses are reused
+* settings are in [W3C] section and apply on reload
+*/
+
+use crate::{
+  common::conf,
+  stains::cyber::types::*
+};
+
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use tokio::sync::Mutex;
+
+use std::{
+  collections::HashMap,
+  fmt,
+  time::{ Duration, Instant }
+};
+
+#[derive(Debug)]
+pub enum Error {
+  /// connection failed or timed out
+  Http(reqwest::Error),
+  /// service answered with error status
+  Status(u16, String),
+  /// `api` from config can't be used as base url
+  Url(String),
+  /// answer doesn't look like expected
+  Json(String, serde_json::Error)
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::Http(why)       => write!(f, "w3champions request failed: {}", why),
+      Error::Status(code, p) => write!(f, "w3champions answered {} on {}", code, p),
+      Error::Url(api)        => write!(f, "bad w3champions api url {}", api),
+      Error::Json(p, why)    => write!(f, "unexpected w3champions answer on {}: {}", p, why)
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+  fn from(why: reqwest::Error) -> Error { Error::Http(why) }
+}
+
+/// matches in one page of player history
+pub static MATCHES_PAGE : u32 = 50;
+
+/// first retry waits that long, every next one twice longer
+static BACKOFF : Duration = Duration::from_millis(500);
+
+lazy_static! {
+  static ref HTTP: reqwest::Client = reqwest::Client::new();
+  /// earliest time next request could be sent
+  static ref NEXT_REQUEST: Mutex<Instant> = Mutex::new(Instant::now());
+  /// url → (time of response, response)
+  static ref CACHE: Mutex<HashMap<String, (Instant, String)>> = Mutex::new(HashMap::new());
+}
+
+/// waits for own turn, requests are spread evenly
+async fn rate_limit(per_second: u32) {
+  let wait = {
+    let mut next = NEXT_REQUEST.lock().await;
+    let now = Instant::now();
+    let start = if *next > now { *next } else { now };
+    *next = start + Duration::from_secs(1) / per_second.max(1);
+    start - now
+  };
+  if wait > Duration::from_millis(0) {
+    tokio::time::delay_for(wait).await;
+  }
+}
+
+async fn cached(url: &str, ttl: Duration) -> Option<String> {
+  let cache = CACHE.lock().await;
+  cache.get(url).filter(|(at, _)| at.elapsed() < ttl).map(|(_, body)| body.clone())
+}
+
+async fn remember(url: &str, body: &str, ttl: Duration) {
+  let mut cache = CACHE.lock().await;
+  cache.retain(|_, (at, _)| at.elapsed() < ttl);
+  cache.insert(url.to_string(), (Instant::now(), body.to_string()));
+}
+
+/// server errors and throttling are worth another try
+fn retryable(status: reqwest::StatusCode) -> bool {
+  status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+/// every segment and query value is percent-encoded
+fn url(api: &str, segments: &[&str], query: &[(&str, &str)]) -> Result<Url, Error> {
+  let mut url = Url::parse(api).map_err(|_| Error::Url(api.to_string()))?;
+  url.path_segments_mut().map_err(|_| Error::Url(api.to_string()))?
+     .pop_if_empty().extend(segments);
+  if !query.is_empty() {
+    url.query_pairs_mut().extend_pairs(query);
+  }
+  Ok(url)
+}
+
+async fn get_text(segments: &[&str], query: &[(&str, &str)]) -> Result<String, Error> {
+  let opts = conf::current();
+  let ttl = Duration::from_secs(opts.w3c_cache_ttl);
+  let url = url(opts.w3c_api.as_str(), segments, query)?;
+  if let Some(body) = cached(url.as_str(), ttl).await {
+    return Ok(body);
+  }
+  // path is enough to tell requests apart in errors
+  let path = match url.query() {
+    Some(query) => format!("{}?{}", url.path(), query),
+    None => url.path().to_string()
+  };
+  let mut attempt = 0;
+  loop {
+    rate_limit(opts.w3c_rate_limit).await;
+    let result = HTTP.get(url.clone())
+                     .timeout(Duration::from_secs(opts.w3c_timeout))
+                     .send().await;
+    let error = match result {
+      Ok(res) if res.status().is_success() => {
+        let body = res.text().await?;
+        remember(url.as_str(), &body, ttl).await;
+        return Ok(body);
+      },
+      Ok(res) if retryable(res.status()) => Error::Status(res.status().as_u16(), path.clone()),
+      Ok(res) => return Err(Error::Status(res.status().as_u16(), path)),
+      Err(why) => Error::Http(why)
+    };
+    if attempt >= opts.w3c_retries {
+      return Err(error);
+    }
+    warn!("{}, retrying", error);
+    tokio::time::delay_for(BACKOFF * 2u32.pow(attempt)).await;
+    attempt += 1;
+  }
+}
+
+async fn get<T: DeserializeOwned>(segments: &[&str], query: &[(&str, &str)]) -> Result<T, Error> {
+  let body = get_text(segments, query).await?;
+  serde_json::from_str(body.as_str()).map_err(|why| Error::Json(segments.join("/"), why))
+}
+
+pub async fn seasons() -> Result<Vec<Season>, Error> {
+  get(&["ladder", "seasons"], &[]).await
+}
+
+pub async fn search(name: &str, season: &str, gateway: u32) -> Result<Vec<Search>, Error> {
+  let gateway = gateway.to_string();
+  get(&["ladder", "search"], &[ ("gateWay", gateway.as_str())
+                              , ("searchFor", name)
+                              , ("season", season) ]).await
+}
+
+pub async fn game_mode_stats(battletag: &str, season: &str, gateway: u32) -> Result<Vec<GMStats>, Error> {
+  let gateway = gateway.to_string();
+  get(&["players", battletag, "game-mode-stats"], &[ ("gateWay", gateway.as_str())
+                                                   , ("season", season) ]).await
+}
+
+pub async fn race_stats(battletag: &str, season: &str, gateway: u32) -> Result<Vec<Stats>, Error> {
+  let gateway = gateway.to_string();
+  get(&["players", battletag, "race-stats"], &[ ("gateWay", gateway.as_str())
+                                              , ("season", season) ]).await
+}
+
+pub async fn race_on_map(battletag: &str, season: &str) -> Result<Stats2, Error> {
+  get(&["player-stats", battletag, "race-on-map-versus-race"], &[("season", season)]).await
+}
+
+/// matches being played now, all modes without `game_mode`
+pub async fn ongoing(gateway: u32, game_mode: Option<u32>) -> Result<Going, Error> {
+  let gateway = gateway.to_string();
+  match game_mode {
+    Some(mode) => {
+      let mode = mode.to_string();
+      get(&["matches", "ongoing"], &[ ("offset", "0")
+                                    , ("gateway", gateway.as_str())
+                                    , ("gameMode", mode.as_str()) ]).await
+    },
+    None => get(&["matches", "ongoing"], &[("offset", "0"), ("gateway", gateway.as_str())]).await
+  }
+}
+
+/// finished matches of player, newest first, `MATCHES_PAGE` at once
+pub async fn player_matches(battletag: &str, gateway: u32, season: &str, offset: u32) -> Result<Going, Error> {
+  set!{ gateway   = gateway.to_string()
+      , offset    = offset.to_string()
+      , page_size = MATCHES_PAGE.to_string() };
+  get(&["matches", "search"], &[ ("playerId", battletag)
+                               , ("gateway", gateway.as_str())
+                               , ("offset", offset.as_str())
+                               , ("pageSize", page_size.as_str())
+                               , ("season", season) ]).await
+}
+
+pub async fn match_details(match_id: &str) -> Result<MD, Error> {
+  get(&["matches", match_id], &[]).await
+}
+
+/// clan of player, `None` if there is no clan
+pub async fn clan(battletag: &str) -> Result<Option<String>, Error> {
+  match get::<Value>(&["clans"], &[("battleTag", battletag)]).await {
+    Ok(clan) => Ok(clan.pointer("/clanId").and_then(|c| c.as_str()).map(String::from)),
+    // players without clan get 404
+    Err(Error::Status(404, _)) => Ok(None),
+    Err(why) => Err(why)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::common::types::AOptions;
+
+  use tokio::{
+    net::TcpListener,
+    io::{ AsyncReadExt, AsyncWriteExt }
+  };
+
+  use std::sync::Arc;
+
+  /// answers of mock service by request target, every next request gets next answer
+  fn answers(target: &str) -> Vec<(u16, &'static str)> {
+    match target {
+      "/api/ladder/seasons" => vec![(500, ""), (503, ""), (200, r#"[{"id":3}]"#)],
+      "/api/clans?battleTag=Lonely%231234" => vec![(404, "")],
+      "/api/clans?battleTag=Cl%26n+Man%231234" => vec![(200, r#"{"clanId":"ABC"}"#), (500, "")],
+      _ => vec![(418, "")]
+    }
+  }
+
+  /// w3champions mock on local port, counts requests of every target
+  async fn mock() -> (String, Arc<Mutex<HashMap<String, usize>>>) {
+    let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let api = format!("http://{}/api", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(HashMap::new()));
+    let counter = requests.clone();
+    tokio::spawn(async move {
+      loop {
+        let (mut socket, _) = match listener.accept().await {
+          Ok(accepted) => accepted,
+          Err(_) => return
+        };
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+          match socket.read(&mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => request.extend_from_slice(&buffer[..n])
+          }
+        }
+        let request = String::from_utf8_lossy(&request).to_string();
+        let target = request.split_whitespace().nth(1).unwrap_or("").to_string();
+        let n = {
+          let mut counter = counter.lock().await;
+          let n = counter.entry(target.clone()).or_insert(0);
+          *n += 1;
+          *n
+        };
+        let answers = answers(&target);
+        let (status, body) = answers[(n - 1).min(answers.len() - 1)];
+        let response = format!("HTTP/1.1 {} MOCK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}"
+                              , status, body.len(), body);
+        let _ = socket.write_all(response.as_bytes()).await;
+      }
+    });
+    (api, requests)
+  }
+
+  async fn count(requests: &Arc<Mutex<HashMap<String, usize>>>, target: &str) -> usize {
+    requests.lock().await.get(target).cloned().unwrap_or(0)
+  }
+
+  // one test since configuration and cache are global
+  #[tokio::test]
+  async fn client_against_mock() {
+    let (api, requests) = mock().await;
+    conf::set_current(AOptions { w3c_api: api
+                               , w3c_timeout: 5
+                               , w3c_retries: 2
+                               , w3c_rate_limit: 100
+                               , w3c_cache_ttl: 60
+                               , ..AOptions::default() });
+
+    // server errors are retried
+    let seasons = seasons().await.unwrap();
+    assert_eq!(seasons.iter().map(|s| s.id).collect::<Vec<u32>>(), vec![3]);
+    assert_eq!(count(&requests, "/api/ladder/seasons").await, 3);
+
+    // no clan is not an error
+    assert_eq!(clan("Lonely#1234").await.unwrap(), None);
+    assert_eq!(count(&requests, "/api/clans?battleTag=Lonely%231234").await, 1);
+
+    // query is encoded and fresh answer is reused, mock would fail second time
+    assert_eq!(clan("Cl&n Man#1234").await.unwrap(), Some(String::from("ABC")));
+    assert_eq!(clan("Cl&n Man#1234").await.unwrap(), Some(String::from("ABC")));
+    assert_eq!(count(&requests, "/api/clans?battleTag=Cl%26n+Man%231234").await, 1);
+  }
+}
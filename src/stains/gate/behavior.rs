@@ -1,6 +1,5 @@
 use crate::{
-  common::types::AOptions,
-  common::help::channel::channel_by_name,
+  common::{ types::AOptions, conf, points, settings },
   stains::{
     ai::chain,
     cyber, cyber::types::TrackingGame,
@@ -17,209 +16,267 @@ use serenity::{
 };
 
 use std::{
+  collections::HashSet,
   sync::atomic::Ordering,
   time
 };
 
 use rand::Rng;
+use chrono::Utc;
 
 pub async fn activate(ctx: &Context, options: &AOptions) {
   info!("activation has started");
   // set actual season for pad statistics
   update_current_season().await;
 
-  let last_guild_u64 = options.last_guild.parse::<u64>().unwrap_or(0);
-  if last_guild_u64 != 0 {
-    let guild_id = GuildId( last_guild_u64 );
+  // members who left and didn't come back are forgotten after a while
+  tokio::spawn(async move {
+    loop {
+      match points::purge_archive(conf::current().archive_days).await {
+        Ok(purged) if purged > 0 => info!("purged {} archived points records", purged),
+        Ok(_) => (),
+        Err(why) => error!("Failed to purge archived points {:?}", why)
+      }
+      /* every day */
+      tokio::time::delay_for(time::Duration::from_secs(24*60*60)).await;
+    }
+  });
+
+  if options.guild != 0 {
+    let guild_id = GuildId( options.guild );
 
     // updating ai:chain cache
     chain::update_cache(&ctx, &guild_id).await;
 
-    if let Ok(channels) = guild_id.channels(ctx).await {
-
-      let mut background_threads_successfully_started = false;
-      let version = format!("Version {}", env!("CARGO_PKG_VERSION").to_string());
-      ctx.set_activity(Activity::listening(version.as_str())).await;
-      ctx.idle().await;
-
-      if let Some((channel, _)) = channel_by_name(&ctx, &channels, "main").await {
-        set!{ ch_clone = channel.clone()
-            , ctx_clone = ctx.clone() };
-        tokio::spawn(async move {
-          loop {
-            let activity_level = chain::ACTIVITY_LEVEL.load(Ordering::Relaxed);
-            let rndx = rand::thread_rng().gen_range(0, activity_level);
-            if rndx == 1 {
-              let ai_text = chain::generate_english_or_russian(&ctx_clone, &guild_id).await;
-              if let Err(why) = ch_clone.send_message(&ctx_clone, |m| {
-                m.content(ai_text)
-              }).await {
-                error!("Failed to post periodic message {:?}", why);
-              }
+    let mut background_threads_successfully_started = false;
+    let version = format!("Version {}", env!("CARGO_PKG_VERSION").to_string());
+    ctx.set_activity(Activity::listening(version.as_str())).await;
+    ctx.idle().await;
+
+    // main channel is looked up every time so ~config bind and reload apply right away
+    let ctx_chatter = ctx.clone();
+    tokio::spawn(async move {
+      loop {
+        let activity_level = settings::get(*guild_id.as_u64()).await.activity
+                               .unwrap_or(chain::ACTIVITY_LEVEL.load(Ordering::Relaxed));
+        let rndx = rand::thread_rng().gen_range(0, activity_level);
+        if rndx == 1 {
+          if let Some(channel) = settings::main_channel(&ctx_chatter, &guild_id).await {
+            let ai_text = chain::generate_english_or_russian(&ctx_chatter, &guild_id).await;
+            if let Err(why) = channel.send_message(&ctx_chatter, |m| {
+              m.content(ai_text)
+            }).await {
+              error!("Failed to post periodic message {:?}", why);
             }
-            update_current_season().await;
-            /* every 30 minutes */
-            tokio::time::delay_for(time::Duration::from_secs(30*60)).await;
           }
-        });
+        }
+        update_current_season().await;
+        /* every 30 minutes by default */
+        let chatter_interval = conf::current().chatter_interval;
+        tokio::time::delay_for(time::Duration::from_secs(chatter_interval)).await;
       }
+    });
 
-      if let Some((channel, _)) = channel_by_name(&ctx, &channels, "log").await {
-
-        // Delete live games from log channel (if some)
-        for vec_msg in channel.messages(&ctx, |g| g.limit(50)).await {
-          let mut vec_id = Vec::new();
-          for message in vec_msg {
-            for embed in message.embeds {
-              if let Some(title) = embed.title {
-                if title == "LIVE" || title == "JUST STARTED" {
-                  vec_id.push(message.id);
-                  break;
-                }
+    // digests of tracked games, posted soon after day ends
+    let ctx_digest = ctx.clone();
+    tokio::spawn(async move {
+      loop {
+        cyber::history::digests(&ctx_digest, &guild_id).await;
+        let wait = cyber::history::until_tomorrow() + 60;
+        tokio::time::delay_for(time::Duration::from_secs(wait)).await;
+      }
+    });
+
+    // games tracked before restart are resumed
+    let resumed = cyber::team_checker::restore_games().await;
+    if resumed.len() > 0 {
+      info!("resuming {} tracked games", resumed.len());
+    }
+    let resumed_ids = resumed.values().map(|t| t.tracking_msg_id).collect::<HashSet<u64>>();
+
+    // Delete live games which can't be resumed from log channel (if some)
+    if let Some(channel) = settings::log_channel(&ctx, &guild_id).await {
+      for vec_msg in channel.messages(&ctx, |g| g.limit(50)).await {
+        let mut vec_id = Vec::new();
+        for message in vec_msg {
+          if resumed_ids.contains(message.id.as_u64()) {
+            continue;
+          }
+          for embed in message.embeds {
+            if let Some(title) = embed.title {
+              if title == "LIVE" || title == "JUST STARTED" {
+                vec_id.push(message.id);
+                break;
               }
             }
           }
-          if vec_id.len() > 0 {
-            match channel.delete_messages(&ctx, vec_id.as_slice()).await {
-              Ok(nothing)  => nothing,
-              Err(err) => warn!("Failed to clean live messages {}", err),
-            };
-          }
         }
+        if vec_id.len() > 0 {
+          match channel.delete_messages(&ctx, vec_id.as_slice()).await {
+            Ok(nothing)  => nothing,
+            Err(err) => warn!("Failed to clean live messages {}", err),
+          };
+        }
+      }
+    }
 
-        set!{ ch_clone = channel.clone(),
-              ctx_clone = ctx.clone(),
-              ch_ud = ch_clone.as_u64().clone(),
-              options_clone = options.clone() };
-
-        tokio::spawn(async move {
-          let mut games_lock = cyber::team_checker::GAMES.lock().await;
-          loop {
-            let mut k_to_del : Vec<String> = Vec::new();
-            for (k, track) in games_lock.iter_mut() {
-              if track.passed_time < 666 {
-                track.passed_time += 1;
-                track.still_live = false;
-              } else {
-                k_to_del.push(k.clone());
-              }
-            }
-            for ktd in k_to_del {
-              warn!("match {} out with timeout", ktd);
-              games_lock.remove(ktd.as_str());
-            }
-            info!("check");
-            if !background_threads_successfully_started {
-              ctx_clone.set_activity(Activity::playing(version.as_str())).await;
-              ctx_clone.online().await;
+    // tracker runs even without log channel, it could be bound or configured later
+    let ctx_clone = ctx.clone();
+
+    tokio::spawn(async move {
+      let mut games_lock = cyber::team_checker::GAMES.lock().await;
+      games_lock.extend(resumed);
+      // games are saved only when some are found or gone, start times don't change
+      let mut saved : HashSet<String> = games_lock.keys().cloned().collect();
+      // warned once until channel is back
+      let mut no_log_channel = false;
+      loop {
+        let mut k_to_del : Vec<String> = Vec::new();
+        for (k, track) in games_lock.iter_mut() {
+          if cyber::team_checker::tracked_seconds(track) < cyber::team_checker::TRACKING_TIMEOUT {
+            track.still_live = false;
+          } else {
+            k_to_del.push(k.clone());
+          }
+        }
+        for ktd in k_to_del {
+          warn!("match {} out with timeout", ktd);
+          games_lock.remove(ktd.as_str());
+        }
+        info!("check");
+        if !background_threads_successfully_started {
+          ctx_clone.set_activity(Activity::playing(version.as_str())).await;
+          ctx_clone.online().await;
+        }
+        background_threads_successfully_started = true;
+        // log channel is looked up every time so ~config bind and reload apply to new games
+        let ch_clone = match settings::log_channel(&ctx_clone, &guild_id).await {
+          Some(channel) => {
+            no_log_channel = false;
+            channel
+          },
+          None => {
+            if !no_log_channel {
+              warn!("no log channel, games are not tracked until it's bound or configured");
+              no_log_channel = true;
             }
-            background_threads_successfully_started = true;
-            let our_gsx = cyber::team_checker::check(&ctx_clone, ch_ud, &mut games_lock).await;
-            for game in our_gsx {
-              let game_key = game.key.clone();
-              if let Ok(user) = ctx_clone.http.get_user(game.player.discord).await {
-
-                setm!{ twitch_live        = false
-                     , additional_fields  = Vec::new()
-                     , image              = None
-                     , em_url             = None };
-
-                if game.player.streams.is_some() {
-                  set! { streams = game.player.streams.clone().unwrap()
-                       , twitch = &streams.twitch
-                       , ggru = &streams.ggru };
-
-                  if twitch.is_some() {
-                    let client = reqwest::Client::new();
-                    let getq = format!("https://api.twitch.tv/helix/streams?user_login={}", twitch.unwrap());
-                    if let Ok(res) = client
-                      .get(getq.as_str())
-                      .header("Authorization", options_clone.twitch_oauth.clone())
-                      .header("Client-ID", options_clone.twitch_client_id.clone())
-                      .send().await {
-                      match res.json::<cyber::twitch::Twitch>().await {
-                        Ok(t) => {
-                          if t.data.len() > 0 {
-                            let d = &t.data[0];
-                            let url = format!("https://www.twitch.tv/{}", d.user_name);
-                            let pic = d.thumbnail_url.replace("{width}", "800")
-                                                     .replace("{height}", "450");
-                            if d.type_string == "live" {
-                              additional_fields.push(("Live on twitch", d.title.clone(), false));
-                              image = Some(pic);
-                              em_url = Some(url);
-                              twitch_live = true;
-                            }
-                          }
-                        }, Err(why) => {
-                          error!("Failed to parse twitch structs {:?}", why);
+            let live_check_interval = conf::current().live_check_interval;
+            tokio::time::delay_for(time::Duration::from_secs(live_check_interval)).await;
+            continue;
+          }
+        };
+        let our_gsx = cyber::team_checker::check(&ctx_clone, *ch_clone.as_u64(), &mut games_lock).await;
+        for game in our_gsx {
+          let game_key = game.key.clone();
+          if let Ok(user) = ctx_clone.http.get_user(game.player.discord).await {
+
+            setm!{ twitch_live        = false
+                 , additional_fields  = Vec::new()
+                 , image              = None
+                 , em_url             = None };
+
+            if game.player.streams.is_some() {
+              set! { streams = game.player.streams.clone().unwrap()
+                   , twitch = streams.twitch.as_deref()
+                   , ggru = streams.ggru.as_deref() };
+
+              if twitch.is_some() {
+                let current = conf::current();
+                let client = reqwest::Client::new();
+                let getq = format!("https://api.twitch.tv/helix/streams?user_login={}", twitch.unwrap());
+                if let Ok(res) = client
+                  .get(getq.as_str())
+                  .header("Authorization", current.twitch_oauth.clone())
+                  .header("Client-ID", current.twitch_client_id.clone())
+                  .send().await {
+                  match res.json::<cyber::twitch::Twitch>().await {
+                    Ok(t) => {
+                      if t.data.len() > 0 {
+                        let d = &t.data[0];
+                        let url = format!("https://www.twitch.tv/{}", d.user_name);
+                        let pic = d.thumbnail_url.replace("{width}", "800")
+                                                 .replace("{height}", "450");
+                        if d.type_string == "live" {
+                          additional_fields.push(("Live on twitch", d.title.clone(), false));
+                          image = Some(pic);
+                          em_url = Some(url);
+                          twitch_live = true;
                         }
                       }
+                    }, Err(why) => {
+                      error!("Failed to parse twitch structs {:?}", why);
                     }
                   }
+                }
+              }
 
-                  if ggru.is_some() {
-                    let ggru_link = format!("http://api2.goodgame.ru/v2/streams/{}", ggru.unwrap());
-                    if let Ok(gg) = reqwest::get(ggru_link.as_str()).await {
-                      match gg.json::<cyber::goodgame::GoodGameData>().await {
-                        Ok(ggdata) => {
-                          if ggdata.status == "Live" {
-                            let url = format!("https://goodgame.ru/channel/{}", ggru.unwrap());
-                            if twitch_live {
-                              let titurl =
-                                format!("{}\n{}", ggdata.channel.title.as_str(), url);
-                              additional_fields.push(("Live on ggru", titurl, false));
-                            } else {
-                              additional_fields.push(("Live on ggru", ggdata.channel.title.clone(), false));
-                              image = Some(ggdata.channel.thumb.clone());
-                              em_url = Some(url);
-                            }
-                          }
-                        }, Err(why) => {
-                          error!("Failed to parse good game structs {:?}", why);
+              if ggru.is_some() {
+                let ggru_link = format!("http://api2.goodgame.ru/v2/streams/{}", ggru.unwrap());
+                if let Ok(gg) = reqwest::get(ggru_link.as_str()).await {
+                  match gg.json::<cyber::goodgame::GoodGameData>().await {
+                    Ok(ggdata) => {
+                      if ggdata.status == "Live" {
+                        let url = format!("https://goodgame.ru/channel/{}", ggru.unwrap());
+                        if twitch_live {
+                          let titurl =
+                            format!("{}\n{}", ggdata.channel.title.as_str(), url);
+                          additional_fields.push(("Live on ggru", titurl, false));
+                        } else {
+                          additional_fields.push(("Live on ggru", ggdata.channel.title.clone(), false));
+                          image = Some(ggdata.channel.thumb.clone());
+                          em_url = Some(url);
                         }
-                      };
+                      }
+                    }, Err(why) => {
+                      error!("Failed to parse good game structs {:?}", why);
                     }
-                  }
-
+                  };
                 }
+              }
 
-                match ch_clone.send_message(&ctx_clone, |m| m
-                  .embed(|e| {
-                    let mut e = e
-                      .title("JUST STARTED")
-                      .author(|a| a.icon_url(&user.face()).name(&user.name))
-                      .description(game.description.as_str());
-                    if additional_fields.len() > 0 {
-                      e = e.fields(additional_fields);
-                    }
-                    if image.is_some() {
-                      e = e.image(image.unwrap());
-                    }
-                    if em_url.is_some() {
-                      e = e.url(em_url.unwrap());
-                    }
-                    e
-                  }
-                )).await {
-                  Ok(msg_id) => {
-                    games_lock.insert(game_key, TrackingGame {
-                      tracking_msg_id: msg_id.id.as_u64().clone(),
-                      passed_time: 0,
-                      still_live: false,
-                      player: game.player }
-                    );
-                  },
-                  Err(why) => {
-                    error!("Failed to post live match {:?}", why);
-                  }
+            }
+
+            match ch_clone.send_message(&ctx_clone, |m| m
+              .embed(|e| {
+                let mut e = e
+                  .title("JUST STARTED")
+                  .author(|a| a.icon_url(&user.face()).name(&user.name))
+                  .description(game.description.as_str());
+                if additional_fields.len() > 0 {
+                  e = e.fields(additional_fields);
+                }
+                if image.is_some() {
+                  e = e.image(image.unwrap());
                 }
+                if em_url.is_some() {
+                  e = e.url(em_url.unwrap());
+                }
+                e
+              }
+            )).await {
+              Ok(msg_id) => {
+                games_lock.insert(game_key, TrackingGame {
+                  tracking_msg_id: msg_id.id.as_u64().clone(),
+                  channel: *ch_clone.as_u64(),
+                  started: Utc::now().timestamp(),
+                  still_live: false,
+                  player: game.player }
+                );
+              },
+              Err(why) => {
+                error!("Failed to post live match {:?}", why);
               }
             }
-            tokio::time::delay_for(time::Duration::from_secs(30)).await;
           }
-        });
+        }
+        let tracked : HashSet<String> = games_lock.keys().cloned().collect();
+        if tracked != saved {
+          cyber::team_checker::save_games(&games_lock).await;
+          saved = tracked;
+        }
+        let live_check_interval = conf::current().live_check_interval;
+        tokio::time::delay_for(time::Duration::from_secs(live_check_interval)).await;
       }
-    }
+    });
   }
 }